@@ -1,9 +1,64 @@
-use std::cell::{Cell, RefCell};
-use std::collections::{HashMap, HashSet};
-use std::fmt::Debug;
+use std::cell::{Cell, Ref, RefCell, RefMut};
+use std::cmp::Reverse;
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::fmt::{self, Debug, Display};
+use std::future::Future;
+use std::hash::{Hash, Hasher};
+use std::ops::{Add, Sub};
+use std::panic::{self, AssertUnwindSafe};
+use std::pin::Pin;
+use std::rc::Rc;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+mod sync;
+
+pub use sync::SyncReactor;
+
+/// Declares cells on an existing reactor with named bindings, e.g.
+///
+/// ```
+/// # fn main() -> Result<(), react::CreateComputeError> {
+/// let mut r = react::Reactor::new();
+/// react::reactor! { r;
+///     a = input(1);
+///     b = input(2);
+///     c = compute(a, b => |x, y| x + y);
+/// }
+/// assert_eq!(r.value(c.into()), Some(3));
+/// # Ok(())
+/// # }
+/// ```
+///
+/// Each binding becomes a local holding the ID returned by the matching `create_*` call, and the
+/// compute closure takes one positional argument per dependency. Compute cells are created with
+/// `?`, so the macro must be used in a function whose error type can be built from the
+/// `create_compute` error.
+#[macro_export]
+macro_rules! reactor {
+    ($r:ident;) => {};
+    ($r:ident; $name:ident = input($init:expr); $($rest:tt)*) => {
+        let $name = $r.create_input($init);
+        $crate::reactor!($r; $($rest)*);
+    };
+    ($r:ident; $name:ident = compute($($dep:ident),+ => $f:expr); $($rest:tt)*) => {
+        let $name = {
+            let f = $f;
+            $r.create_compute(&[$($crate::CellID::from($dep)),+], move |v| {
+                // one argument per dependency, in order, so a dependency can be named twice
+                let mut args = v.iter().cloned();
+                f($({ let _ = stringify!($dep); args.next().unwrap() }),+)
+            })?
+        };
+        $crate::reactor!($r; $($rest)*);
+    };
+}
 
 /// `InputCellID` is a unique identifier for an input cell.
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct InputCellID(usize);
 /// `ComputeCellID` is a unique identifier for a compute cell.
 /// Values of type `InputCellID` and `ComputeCellID` should not be mutually assignable,
@@ -19,307 +74,3878 @@ pub struct InputCellID(usize);
 /// let input = r.create_input(111);
 /// let compute: react::InputCellID = r.create_compute(&[react::CellID::Input(input)], |_| 222).unwrap();
 /// ```
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct ComputeCellID(usize);
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct CallbackID(usize);
 
-pub type Callback<'reactor, T> = RefCell<Box<dyn 'reactor + FnMut(T)>>;
+/// A registered callback. It's handed a reference to the cell's new value and to the reactor, and
+/// returns whether it should stay registered after firing.
+///
+/// ```
+/// use std::cell::RefCell;
+///
+/// let seen = RefCell::new(Vec::new());
+/// let log: react::Callback<i32> = RefCell::new(Box::new(|v, r| {
+///     seen.borrow_mut().push((*v, r.len()));
+///     true
+/// }));
+/// let mut r = react::Reactor::new();
+/// r.create_input(0);
+/// assert!((*log.borrow_mut())(&1, &r));
+/// assert!((*log.borrow_mut())(&2, &r));
+/// assert_eq!(*seen.borrow(), vec![(1, 1), (2, 1)]);
+/// ```
+pub type Callback<'reactor, T> =
+    RefCell<Box<dyn 'reactor + FnMut(&T, &Reactor<'reactor, T>) -> bool>>;
+/// A callback as accepted by `Reactor::add_callbacks`.
+pub type BoxedCallback<'reactor, T> = Box<dyn 'reactor + FnMut(T)>;
+/// A compute function as supplied to `Reactor::from_snapshot`.
+pub type BoxedComputeFn<'reactor, T> = Box<dyn 'reactor + Fn(&[T]) -> T>;
+/// The side effect of an async callback, as returned by `Reactor::take_futures`. The reactor never
+/// polls these itself; it's up to the caller to run them, e.g.
+///
+/// ```
+/// # use std::future::Future;
+/// # use std::sync::Arc;
+/// # use std::task::{Context, Poll, Wake};
+/// # struct NoopWaker;
+/// # impl Wake for NoopWaker {
+/// #     fn wake(self: Arc<Self>) {}
+/// # }
+/// # fn block_on<F: Future>(f: F) -> F::Output {
+/// #     let waker = Arc::new(NoopWaker).into();
+/// #     let mut cx = Context::from_waker(&waker);
+/// #     let mut f = Box::pin(f);
+/// #     loop {
+/// #         if let Poll::Ready(v) = f.as_mut().poll(&mut cx) {
+/// #             return v;
+/// #         }
+/// #     }
+/// # }
+/// use std::cell::RefCell;
+///
+/// let sent = RefCell::new(Vec::new());
+/// let mut r = react::Reactor::new();
+/// let input = r.create_input(1);
+/// let double = r
+///     .create_compute(&[react::CellID::Input(input)], |v| v[0] * 2)
+///     .unwrap();
+/// r.add_async_callback(double, |v| {
+///     let sent = &sent;
+///     async move { sent.borrow_mut().push(v) }
+/// });
+/// r.set_value(input, 2);
+/// for f in r.take_futures() {
+///     block_on(f);
+/// }
+/// assert_eq!(*sent.borrow(), vec![4]);
+/// ```
+pub type BoxedFuture<'reactor> = Pin<Box<dyn 'reactor + Future<Output = ()>>>;
+type ComputeFn<'reactor, T> = Rc<dyn 'reactor + Fn(&[T]) -> T>;
+type FallibleFn<'reactor, T> = Rc<dyn 'reactor + Fn(&[T]) -> Result<T, String>>;
+type FoldFn<'reactor, T> = Rc<RefCell<dyn 'reactor + FnMut(&T, &[T]) -> T>>;
+type DynamicFn<'reactor, T> = Rc<dyn 'reactor + Fn(&DynCtx<'_, T>) -> T>;
+type EqFn<'reactor, T> = Rc<dyn 'reactor + Fn(&T, &T) -> bool>;
+type GlobalCallback<'reactor, T> = Box<dyn 'reactor + FnMut(&[(ComputeCellID, T)])>;
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum CellID {
     Input(InputCellID),
     Compute(ComputeCellID),
 }
 
+impl From<InputCellID> for CellID {
+    fn from(id: InputCellID) -> Self {
+        CellID::Input(id)
+    }
+}
+
+impl From<ComputeCellID> for CellID {
+    fn from(id: ComputeCellID) -> Self {
+        CellID::Compute(id)
+    }
+}
+
 #[derive(Debug, PartialEq)]
 pub enum RemoveCallbackError {
     NonexistentCell,
     NonexistentCallback,
 }
 
-struct InputCell<T> {
-    clients: HashSet<ComputeCellID>,
-    value: T,
+#[derive(Debug, PartialEq)]
+pub enum RemoveCellError {
+    NonexistentCell,
+    /// These compute cells still depend on the cell.
+    HasDependents(Vec<CellID>),
 }
 
-impl<T: Copy + Debug + PartialEq> InputCell<T> {
-    pub fn new(init: T) -> Self {
-        InputCell {
-            clients: HashSet::new(),
-            value: init,
+#[derive(Debug, PartialEq)]
+pub enum KeyError {
+    NonexistentCell,
+    /// The key already belongs to this other cell.
+    DuplicateKey(CellID),
+    /// No input cell has this key.
+    UnknownKey(String),
+}
+
+/// `LengthMismatch` is returned by `Reactor::set_input_values` and `Reactor::restore` when
+/// they're given a different number of values than the reactor has input cells, and by
+/// `Checkpoint::diff` when the two checkpoints differ in length.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LengthMismatch {
+    pub expected: usize,
+    pub actual: usize,
+}
+
+/// `CallbackInfo` describes a registered callback, as returned by `Reactor::callbacks_info`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CallbackInfo {
+    pub id: CallbackID,
+    pub priority: i32,
+    pub enabled: bool,
+}
+
+/// `CallbackControl` is returned by a callback added with `Reactor::add_controlled_callback` to
+/// say whether it should stay registered after firing.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CallbackControl {
+    Keep,
+    Remove,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ComputeError {
+    /// The cell's function panicked, with this message.
+    Panicked(String),
+    /// The cell's fallible function returned an error, with this message.
+    Failed(String),
+    MissingDependency(CellID),
+    /// A compute cell this one depends on failed to evaluate.
+    FailedDependency(ComputeCellID),
+}
+
+#[derive(Debug, PartialEq)]
+pub enum CreateComputeError {
+    NonexistentDependency(CellID),
+    /// This dependency depends, transitively, on the cell being created.
+    Cycle(CellID),
+    /// The name given to the cell already belongs to this other cell.
+    DuplicateKey(CellID),
+    NonFiniteWeight(CellID),
+    ZeroTotalWeight,
+}
+
+/// `FullSnapshot` holds the value of every cell in a reactor at one point in time, indexed by
+/// cell ID. A compute cell that had no value, because its function was failing, is None.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct FullSnapshot<T> {
+    pub inputs: Vec<T>,
+    pub computes: Vec<Option<T>>,
+}
+
+impl<T: Clone> FullSnapshot<T> {
+    // Returns the captured value of a cell, or None if the cell wasn't in the reactor or had no
+    // value.
+    pub fn get(&self, id: CellID) -> Option<T> {
+        match id {
+            CellID::Input(InputCellID(idx)) => self.inputs.get(idx).cloned(),
+            CellID::Compute(ComputeCellID(idx)) => self.computes.get(idx).cloned().flatten(),
         }
     }
 }
 
-struct ComputeCell<'r, T: Debug> {
-    fun: Box<dyn 'r + Fn(&[T]) -> T>,
-    deps: Vec<CellID>,
-    callbacks: HashMap<CallbackID, Callback<'r, T>>,
-    prev_val: Cell<Option<T>>,
-    next_cbid: usize, // increases monotonically; increments on adding a callback
-    clients: HashSet<ComputeCellID>,
+/// `Checkpoint` holds the value of every input cell in a reactor at one point in time, indexed by
+/// input cell ID. It's taken by `Reactor::checkpoint` and written back by `Reactor::restore`.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Checkpoint<T> {
+    values: Vec<T>,
 }
 
-impl<'r, T: Copy + Debug + PartialEq + 'r> ComputeCell<'r, T> {
-    pub fn new<F>(fun: F, deps: &[CellID]) -> Self
+impl<T: Clone> Checkpoint<T> {
+    // Returns the captured value of an input cell, or None if the cell wasn't in the reactor.
+    pub fn get(&self, id: InputCellID) -> Option<T> {
+        let InputCellID(idx) = id;
+        self.values.get(idx).cloned()
+    }
+
+    // Lists each input whose value differs between this checkpoint and `other`, as `(id, old,
+    // new)` with the old value from this one, in ID order.
+    //
+    // Returns an Err if the checkpoints don't have the same number of inputs, `expected` being
+    // this one's.
+    pub fn diff(&self, other: &Checkpoint<T>) -> Result<Vec<(InputCellID, T, T)>, LengthMismatch>
     where
-        F: 'r + Fn(&[T]) -> T,
+        T: PartialEq,
     {
-        ComputeCell {
-            fun: Box::new(fun),
-            deps: deps.to_vec(),
-            callbacks: HashMap::new(),
-            prev_val: Cell::new(None),
-            next_cbid: 0,
-            clients: HashSet::new(),
+        if self.values.len() != other.values.len() {
+            return Err(LengthMismatch {
+                expected: self.values.len(),
+                actual: other.values.len(),
+            });
         }
+        Ok(self
+            .values
+            .iter()
+            .zip(other.values.iter())
+            .enumerate()
+            .filter(|(_, (old, new))| old != new)
+            .map(|(idx, (old, new))| (InputCellID(idx), old.clone(), new.clone()))
+            .collect())
     }
+}
 
-    pub fn call(&self, reactor: &Reactor<'r, T>) -> T {
-        let deps = self
-            .deps
-            .iter()
-            .map(|c| reactor.value(*c).unwrap())
-            .collect::<Vec<T>>();
-        let nv = (self.fun)(&deps);
+/// `ReactorSnapshot` captures the structure of a reactor: the value of every input cell and the
+/// dependencies of every compute cell, indexed by cell ID, and which cells were removed. It's
+/// built by `Reactor::to_snapshot` and turned back into a reactor by `Reactor::from_snapshot`.
+///
+/// Compute functions can't be captured, so they must be supplied again when restoring. Nor are
+/// callbacks, keys, staged values, or history captured. A dynamic cell is captured with the
+/// dependencies it read when last evaluated, and is restored as an ordinary compute cell over
+/// them.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ReactorSnapshot<T> {
+    pub inputs: Vec<T>,
+    pub computes: Vec<Vec<CellID>>,
+    pub removed: Vec<CellID>,
+}
 
-        let mut fire_callbacks = false;
+/// `IdRemap` translates the ID of a cell from a reactor merged into another by `Reactor::merge`
+/// into its ID in the merged reactor. `CallbackID`s belong to their cell, so they're unchanged.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IdRemap {
+    inputs: usize,   // how many input cells the merged reactor had beforehand
+    computes: usize, // likewise, compute cells
+}
 
-        if let Some(pv) = self.prev_val.get() {
-            if nv != pv {
-                self.prev_val.set(Some(nv));
-                fire_callbacks = true;
-            }
-        } else {
-            self.prev_val.set(Some(nv));
-            fire_callbacks = true;
+impl IdRemap {
+    pub fn input(&self, id: InputCellID) -> InputCellID {
+        let InputCellID(idx) = id;
+        InputCellID(idx + self.inputs)
+    }
+
+    pub fn compute(&self, id: ComputeCellID) -> ComputeCellID {
+        let ComputeCellID(idx) = id;
+        ComputeCellID(idx + self.computes)
+    }
+
+    pub fn cell(&self, id: CellID) -> CellID {
+        match id {
+            CellID::Input(iid) => CellID::Input(self.input(iid)),
+            CellID::Compute(cid) => CellID::Compute(self.compute(cid)),
         }
+    }
+}
 
-        if fire_callbacks {
-            for c in self.callbacks.values() {
-                (&mut *c.borrow_mut())(nv);
+/// `TopologyDiff` describes how a reactor's graph differs from another's. Cells are matched by
+/// ID, and each edge runs from a dependency to the compute cell that reads it.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TopologyDiff {
+    pub added_edges: Vec<(CellID, ComputeCellID)>,
+    pub removed_edges: Vec<(CellID, ComputeCellID)>,
+    pub input_delta: isize,
+    pub compute_delta: isize,
+}
+
+/// `CachePolicy` controls how `Reactor::value_with_policy` treats a compute cell's cached value.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CachePolicy {
+    /// Return the cached value without evaluating anything.
+    Always,
+    /// Evaluate from scratch, neither reading nor updating any cached value.
+    Never,
+    /// Re-evaluate the cell, even if its cached value is current, and keep the result as its
+    /// cached value. Dependencies are read as `value` reads them.
+    Session,
+}
+
+/// `Nudge` moves a value one unit up or down, for `Reactor::minimal_affecting_inputs`, or gives
+/// None if that would overflow.
+pub trait Nudge: Sized {
+    fn nudged_up(&self) -> Option<Self>;
+    fn nudged_down(&self) -> Option<Self>;
+}
+
+macro_rules! nudge_int {
+    ($($t:ty),*) => {$(
+        impl Nudge for $t {
+            fn nudged_up(&self) -> Option<Self> {
+                self.checked_add(1)
+            }
+            fn nudged_down(&self) -> Option<Self> {
+                self.checked_sub(1)
             }
         }
+    )*};
+}
 
-        nv
-    }
+nudge_int!(i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize);
+
+macro_rules! nudge_float {
+    ($($t:ty),*) => {$(
+        impl Nudge for $t {
+            fn nudged_up(&self) -> Option<Self> {
+                Some(self + 1.0)
+            }
+            fn nudged_down(&self) -> Option<Self> {
+                Some(self - 1.0)
+            }
+        }
+    )*};
 }
 
-#[derive(Default)]
-pub struct Reactor<'r, T: Debug> {
-    input_cells: Vec<InputCell<T>>,
-    compute_cells: Vec<ComputeCell<'r, T>>,
+nudge_float!(f32, f64);
+
+/// `DynCtx` is handed to the function of a dynamic compute cell (see
+/// `Reactor::create_dynamic`), which reads other cells through it. Every cell read becomes a
+/// dependency of the dynamic cell until its next evaluation.
+pub struct DynCtx<'a, T> {
+    read: &'a dyn Fn(CellID) -> Option<T>,
+    limit: usize, // only compute cells below this index may be read
+    reads: RefCell<Vec<CellID>>,
 }
 
-// You are guaranteed that Reactor will only be tested against types that are Copy + PartialEq.
-impl<'r, T: Copy + Debug + PartialEq + 'r> Reactor<'r, T> {
-    pub fn new() -> Self {
-        Reactor {
-            input_cells: Vec::new(),
-            compute_cells: Vec::new(),
+impl<'a, T> DynCtx<'a, T> {
+    // Retrieves the value of a cell, or None if the cell does not exist or was created after the
+    // dynamic cell reading it.
+    pub fn value(&self, id: CellID) -> Option<T> {
+        if let CellID::Compute(ComputeCellID(idx)) = id {
+            if idx >= self.limit {
+                return None;
+            }
+        }
+        let v = (self.read)(id)?;
+        let mut reads = self.reads.borrow_mut();
+        if !reads.contains(&id) {
+            reads.push(id);
         }
+        Some(v)
     }
+}
 
-    // Creates an input cell with the specified initial value, returning its ID.
-    pub fn create_input(&mut self, initial: T) -> InputCellID {
-        let idx = self.input_cells.len();
-        let id = InputCellID(idx);
-        self.input_cells.push(InputCell::new(initial));
-        id
+type FormulaFn<'a, T> = dyn 'a + Fn(&[T]) -> T;
+
+// The state of a scratch evaluation: values computed so far, values to use in place of some
+// input cells' own, an optional formula to use in place of one compute cell's own, and, if
+// tracing, each cell evaluated so far with its value.
+struct Scratch<'a, T> {
+    memo: HashMap<ComputeCellID, T>,
+    inputs: HashMap<InputCellID, T>,
+    formula: Option<(ComputeCellID, &'a FormulaFn<'a, T>)>,
+    trace: Option<Vec<(CellID, T)>>,
+}
+
+impl<'a, T> Scratch<'a, T> {
+    fn new() -> Self {
+        Scratch {
+            memo: HashMap::new(),
+            inputs: HashMap::new(),
+            formula: None,
+            trace: None,
+        }
     }
+}
 
-    // Creates a compute cell with the specified dependencies and compute function.
-    // The compute function is expected to take in its arguments in the same order as specified in
-    // `dependencies`.
-    // You do not need to reject compute functions that expect more arguments than there are
-    // dependencies (how would you check for this, anyway?).
-    //
-    // If any dependency doesn't exist, returns an Err with that nonexistent dependency.
-    // (If multiple dependencies do not exist, exactly which one is returned is not defined and
-    // will not be tested)
-    //
-    // Notice that there is no way to *remove* a cell.
-    // This means that you may assume, without checking, that if the dependencies exist at creation
-    // time they will continue to exist as long as the Reactor exists.
-    pub fn create_compute<F>(
-        &mut self,
-        dependencies: &[CellID],
-        compute_func: F,
-    ) -> Result<ComputeCellID, CellID>
-    where
-        F: 'r + Fn(&[T]) -> T,
-    {
-        let cidx = self.compute_cells.len();
-        let cid = ComputeCellID(cidx);
+// A standalone copy of the part of a graph a cell depends on, with the inputs' values as of when
+// it was taken, for evaluating the cell later.
+struct Frozen<'r, T> {
+    inputs: HashMap<InputCellID, T>,
+    cells: HashMap<ComputeCellID, (Formula<'r, T>, Vec<CellID>)>,
+}
 
-        for id in dependencies.iter() {
-            match id {
-                CellID::Input(InputCellID(idx)) => {
-                    if *idx >= self.input_cells.len() {
-                        return Err(*id);
-                    }
-                }
-                CellID::Compute(ComputeCellID(idx)) => {
-                    if *idx >= self.compute_cells.len() {
-                        return Err(*id);
-                    }
+impl<'r, T: Clone> Frozen<'r, T> {
+    fn eval(&self, id: CellID, memo: &mut HashMap<ComputeCellID, T>) -> Option<T> {
+        match id {
+            CellID::Input(iid) => self.inputs.get(&iid).cloned(),
+            CellID::Compute(cid) => {
+                if let Some(v) = memo.get(&cid) {
+                    return Some(v.clone());
                 }
+                let (fun, deps) = self.cells.get(&cid)?;
+                let shared = RefCell::new(std::mem::take(memo));
+                let v = fun.apply(deps, &|d| self.eval(d, &mut shared.borrow_mut()));
+                *memo = shared.into_inner();
+                let (v, _) = v?;
+                memo.insert(cid, v.clone());
+                Some(v)
             }
         }
+    }
+}
 
-        // register as clients with all dependencies.
-        for id in dependencies.iter() {
-            match id {
-                CellID::Input(InputCellID(idx)) => {
-                    let _ = self.input_cells[*idx].clients.insert(cid);
-                }
-                CellID::Compute(ComputeCellID(idx)) => {
-                    let _ = self.compute_cells[*idx].clients.insert(cid);
-                }
+/// `SnapshotLog` is a sequence of input states, each holding every input cell's value in
+/// creation order. It carries no compute functions: a log is replayed onto a reactor that the
+/// caller has rebuilt with the same cells.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SnapshotLog<T> {
+    states: Vec<Vec<T>>,
+}
+
+impl<T> SnapshotLog<T> {
+    pub fn new() -> Self {
+        SnapshotLog { states: Vec::new() }
+    }
+
+    pub fn len(&self) -> usize {
+        self.states.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.states.is_empty()
+    }
+}
+
+/// How many generations back `Reactor::value_at_generation` can look.
+pub const GENERATION_WINDOW: u64 = 64;
+
+// The values a cell has had over the last `GENERATION_WINDOW` generations, each paired with the
+// generation it was set in.
+#[derive(Clone)]
+struct History<T> {
+    entries: VecDeque<(u64, T)>,
+}
+
+impl<T: Clone> History<T> {
+    fn new() -> Self {
+        History {
+            entries: VecDeque::new(),
+        }
+    }
+
+    fn record(&mut self, generation: u64, value: T) {
+        if let Some(last) = self.entries.back_mut() {
+            if last.0 == generation {
+                last.1 = value;
+                return;
             }
         }
-        let cell = ComputeCell::new(compute_func, dependencies);
-        cell.call(&self); // set the initial value
-        self.compute_cells.push(cell);
+        self.entries.push_back((generation, value));
+        // keep the newest entry at or before the start of the window, since it's still the
+        // value at that point
+        let start = generation.saturating_sub(GENERATION_WINDOW);
+        while self.entries.len() > 1 && self.entries[1].0 <= start {
+            self.entries.pop_front();
+        }
+    }
 
-        Ok(cid)
+    fn at(&self, generation: u64) -> Option<T> {
+        self.entries
+            .iter()
+            .rev()
+            .find(|(g, _)| *g <= generation)
+            .map(|(_, v)| v.clone())
     }
+}
 
-    // Retrieves the current value of the cell, or None if the cell does not exist.
-    //
-    // You may wonder whether it is possible to implement `get(&self, id: CellID) -> Option<&Cell>`
-    // and have a `value(&self)` method on `Cell`.
-    //
-    // It turns out this introduces a significant amount of extra complexity to this exercise.
-    // We chose not to cover this here, since this exercise is probably enough work as-is.
-    pub fn value(&self, id: CellID) -> Option<T> {
-        match id {
-            CellID::Input(InputCellID(idx)) => self.input_cells.get(idx).map(|i| i.value),
-            CellID::Compute(ComputeCellID(idx)) => {
-                if let Some(cell) = self.compute_cells.get(idx) {
-                    Some(cell.call(&self))
-                } else {
-                    None
-                }
-            }
+/// A 64-bit FNV-1a hasher. Unlike the std `DefaultHasher`, its output is fixed, so hashes can be
+/// compared between processes and builds.
+struct StableHasher(u64);
+
+impl StableHasher {
+    fn new() -> Self {
+        StableHasher(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Hasher for StableHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        for b in bytes {
+            self.0 ^= u64::from(*b);
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
         }
     }
 
-    // Sets the value of the specified input cell.
-    //
-    // Returns false if the cell does not exist.
-    //
-    // Similarly, you may wonder about `get_mut(&mut self, id: CellID) -> Option<&mut Cell>`, with
-    // a `set_value(&mut self, new_value: T)` method on `Cell`.
-    //
-    // As before, that turned out to add too much extra complexity.
-    pub fn set_value(&mut self, id: InputCellID, new_value: T) -> bool {
-        let InputCellID(idx) = id;
-        if idx < self.input_cells.len() {
-            let old_value = self.input_cells[idx].value;
-            if old_value == new_value {
-                return true;
-            }
-            self.input_cells[idx].value = new_value;
-
-            let mut clients1 = self.input_cells[idx].clients.clone();
-            let mut clients2 = HashSet::new();
-
-            let mut done = false;
-
-            // Recursively iterate through all clients until we've converged on the
-            // the stable set of them. Does at least N extra checks, where N is
-            // the numer of ultimate clients.
-            while !done {
-                for client in clients1.iter() {
-                    clients2.insert(client.clone());
-                    let ComputeCellID(idx) = client;
-                    let cell = &self.compute_cells[*idx];
-                    // first find all the clients that will be called without us
-                    clients2.extend(cell.clients.iter());
-                }
-                for client in clients2.iter() {
-                    let ComputeCellID(idx) = client;
-                    let cell = &self.compute_cells[*idx];
-                    clients1.extend(cell.clients.iter());
-                }
-
-                done = clients1 == clients2;
-            }
-
-            // This has the potential to call more clients than needed, but ComputeCells
-            // cache their previous value and only invoke their callbacks on change,
-            // so client callbacks won't get invoked more than once.
-            //
-            // There's an implicit assumption here that each ComputeCell's function is
-            // cheap to run, which is probably not true in general. We could do a
-            // topological sort of the client graph to ensure we only call leaf nodes.
-            for client in clients1 {
-                let ComputeCellID(idx) = client;
-                let cell = &self.compute_cells[idx];
-                cell.call(&self);
-            }
-            // we have set a new value and called all clients, return true
-            true
-        } else {
-            // the new value was the same as the old value, return false
-            false
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+struct InputCell<'r, T> {
+    clients: HashSet<ComputeCellID>,
+    value: T,
+    initial: T,
+    history: History<T>,
+    version: u64, // bumped every time the value changes
+    callbacks: BTreeMap<CallbackID, BoxedCallback<'r, T>>,
+    batched: Option<T>, // the value before the first change in the current batch
+
+    next_cbid: usize, // increases monotonically; increments on adding a callback
+    removed: bool,
+}
+
+impl<'r, T: Clone + Debug + PartialEq> InputCell<'r, T> {
+    pub fn new(init: T, generation: u64) -> Self {
+        let mut history = History::new();
+        history.record(generation, init.clone());
+        InputCell {
+            clients: HashSet::new(),
+            value: init.clone(),
+            initial: init,
+            history,
+            version: 0,
+            callbacks: BTreeMap::new(),
+            batched: None,
+            next_cbid: 0,
+            removed: false,
         }
     }
 
-    // Adds a callback to the specified compute cell.
-    //
-    // Returns the ID of the just-added callback, or None if the cell doesn't exist.
-    //
-    // Callbacks on input cells will not be tested.
-    //
-    // The semantics of callbacks (as will be tested):
-    // For a single set_value call, each compute cell's callbacks should each be called:
-    // * Zero times if the compute cell's value did not change as a result of the set_value call.
-    // * Exactly once if the compute cell's value changed as a result of the set_value call.
-    //   The value passed to the callback should be the final value of the compute cell after the
-    //   set_value call.
-    pub fn add_callback<F: 'r + FnMut(T) -> ()>(
-        &mut self,
-        id: ComputeCellID,
-        callback: F,
-    ) -> Option<CallbackID> {
-        let ComputeCellID(idx) = id;
-        if idx >= self.compute_cells.len() {
-            return None;
+    // Copies the cell, all but its callbacks.
+    fn without_callbacks(&self) -> Self {
+        InputCell {
+            clients: self.clients.clone(),
+            value: self.value.clone(),
+            initial: self.initial.clone(),
+            history: self.history.clone(),
+            version: self.version,
+            callbacks: BTreeMap::new(),
+            batched: self.batched.clone(),
+            next_cbid: self.next_cbid,
+            removed: self.removed,
         }
+    }
 
-        let cidx = self.compute_cells[idx].next_cbid.to_owned();
-        self.compute_cells[idx].next_cbid += 1;
-        let cid = CallbackID(cidx);
+    fn fire(&mut self, firing: &Cell<bool>, value: &T) {
+        let was_firing = firing.replace(true);
+        for callback in self.callbacks.values_mut() {
+            callback(value.clone());
+        }
+        firing.set(was_firing);
+    }
+}
 
-        self.compute_cells[idx]
-            .callbacks
-            .insert(cid, RefCell::new(Box::new(callback)));
+enum Formula<'r, T> {
+    Static(ComputeFn<'r, T>),
+    Fallible(FallibleFn<'r, T>),
+    Fold(FoldFn<'r, T>, Rc<RefCell<T>>), // the step function and the accumulated state
+    Dynamic(DynamicFn<'r, T>, usize),    // the function and the cell's own index
+}
 
-        Some(cid)
+impl<'r, T> Clone for Formula<'r, T> {
+    fn clone(&self) -> Self {
+        match self {
+            Formula::Static(fun) => Formula::Static(Rc::clone(fun)),
+            Formula::Fallible(fun) => Formula::Fallible(Rc::clone(fun)),
+            Formula::Fold(fun, state) => Formula::Fold(Rc::clone(fun), Rc::clone(state)),
+            Formula::Dynamic(fun, limit) => Formula::Dynamic(Rc::clone(fun), *limit),
+        }
     }
+}
 
-    // Removes the specified callback, using an ID returned from add_callback.
-    //
-    // Returns an Err if either the cell or callback does not exist.
-    //
-    // A removed callback should no longer be called.
-    pub fn remove_callback(
-        &mut self,
-        cell: ComputeCellID,
+impl<'r, T: Clone> Formula<'r, T> {
+    // Applies the formula to the values `read` yields for the cells it depends on, or returns
+    // None if a static dependency is missing or a fallible formula fails. A dynamic formula also
+    // returns the cells it read. A fold's state is left alone; see `commit`.
+    fn apply(
+        &self,
+        deps: &[CellID],
+        read: &dyn Fn(CellID) -> Option<T>,
+    ) -> Option<(T, Option<Vec<CellID>>)> {
+        match self {
+            Formula::Static(fun) => {
+                let deps = deps.iter().map(|c| read(*c)).collect::<Option<Vec<T>>>()?;
+                Some((fun(&deps), None))
+            }
+            Formula::Fallible(fun) => {
+                let deps = deps.iter().map(|c| read(*c)).collect::<Option<Vec<T>>>()?;
+                Some((fun(&deps).ok()?, None))
+            }
+            Formula::Fold(fun, state) => {
+                let deps = deps.iter().map(|c| read(*c)).collect::<Option<Vec<T>>>()?;
+                let nv = (*fun.borrow_mut())(&state.borrow(), &deps);
+                Some((nv, None))
+            }
+            Formula::Dynamic(fun, limit) => {
+                let ctx = DynCtx {
+                    read,
+                    limit: *limit,
+                    reads: RefCell::new(Vec::new()),
+                };
+                let nv = fun(&ctx);
+                Some((nv, Some(ctx.reads.into_inner())))
+            }
+        }
+    }
+
+    // Makes `nv`, a value this formula just produced, a fold's new state.
+    fn commit(&self, nv: &T) {
+        if let Formula::Fold(_, state) = self {
+            *state.borrow_mut() = nv.clone();
+        }
+    }
+
+    // Returns the error a fallible formula reports for the values `read` yields, or None if it
+    // succeeds, a dependency is missing, or the formula can't fail.
+    fn error(&self, deps: &[CellID], read: &dyn Fn(CellID) -> Option<T>) -> Option<String> {
+        match self {
+            Formula::Fallible(fun) => {
+                let deps = deps.iter().map(|c| read(*c)).collect::<Option<Vec<T>>>()?;
+                fun(&deps).err()
+            }
+            _ => None,
+        }
+    }
+}
+
+struct CallbackEntry<'r, T: Debug> {
+    fun: Callback<'r, T>,
+    priority: i32,
+    enabled: bool,
+}
+
+struct ComputeCell<'r, T: Debug> {
+    fun: Formula<'r, T>,
+    deps: Vec<CellID>,
+    pending_deps: RefCell<Option<Vec<CellID>>>, // what a dynamic cell read when last cached
+    callbacks: RefCell<HashMap<CallbackID, CallbackEntry<'r, T>>>,
+    prev_val: RefCell<Option<T>>,
+    dirty: Cell<bool>, // set when an upstream input changes; cleared when the value is recomputed
+    changed_at: Cell<u64>, // the reactor's change sequence number as of the last change, or 0
+    history: RefCell<History<T>>,
+    version: Cell<u64>, // bumped every time the value changes after the initial evaluation
+    batched: RefCell<Option<Option<T>>>, // the value before the first change in the current batch
+    eq: Option<EqFn<'r, T>>, // decides whether a new value is a change; `==` if None
+    next_cbid: usize,   // increases monotonically; increments on adding a callback
+    clients: HashSet<ComputeCellID>,
+    rank: usize, // longest distance from any input; see `Reactor::cell_ranks`
+    removed: bool,
+}
+
+impl<'r, T: Clone + Debug + PartialEq + 'r> ComputeCell<'r, T> {
+    pub fn new<F>(fun: F, deps: &[CellID]) -> Self
+    where
+        F: 'r + Fn(&[T]) -> T,
+    {
+        ComputeCell::with_formula(Formula::Static(Rc::new(fun)), deps)
+    }
+
+    fn with_formula(fun: Formula<'r, T>, deps: &[CellID]) -> Self {
+        ComputeCell {
+            fun,
+            deps: deps.to_vec(),
+            pending_deps: RefCell::new(None),
+            callbacks: RefCell::new(HashMap::new()),
+            prev_val: RefCell::new(None),
+            dirty: Cell::new(true),
+            changed_at: Cell::new(0),
+            history: RefCell::new(History::new()),
+            version: Cell::new(0),
+            batched: RefCell::new(None),
+            eq: None,
+            next_cbid: 0,
+            clients: HashSet::new(),
+            rank: 0,
+            removed: false,
+        }
+    }
+
+    // Copies the cell, all but its callbacks. A fold gets its own copy of its accumulated state,
+    // though its step function is shared.
+    fn without_callbacks(&self) -> Self {
+        let fun = match &self.fun {
+            Formula::Fold(fun, state) => Formula::Fold(
+                Rc::clone(fun),
+                Rc::new(RefCell::new(state.borrow().clone())),
+            ),
+            fun => fun.clone(),
+        };
+        ComputeCell {
+            fun,
+            deps: self.deps.clone(),
+            pending_deps: self.pending_deps.clone(),
+            callbacks: RefCell::new(HashMap::new()),
+            prev_val: self.prev_val.clone(),
+            dirty: self.dirty.clone(),
+            changed_at: self.changed_at.clone(),
+            history: self.history.clone(),
+            version: self.version.clone(),
+            batched: self.batched.clone(),
+            eq: self.eq.clone(),
+            next_cbid: self.next_cbid,
+            clients: self.clients.clone(),
+            rank: self.rank,
+            removed: self.removed,
+        }
+    }
+
+    // Returns the cached value, recomputing it first (along with any dirty dependencies) if an
+    // upstream input has changed since it was last computed.
+    //
+    // Returns None, leaving the cache alone, if a dependency can't be read; see `MAX_EVAL_DEPTH`.
+    pub fn call(&self, reactor: &Reactor<'r, T>) -> Option<T> {
+        match (self.dirty.get(), self.cached()) {
+            (false, Some(v)) => Some(v),
+            _ => self.recompute(reactor),
+        }
+    }
+
+    // Returns the cached value, or None if the cell has never been evaluated.
+    fn cached(&self) -> Option<T> {
+        self.prev_val.borrow().clone()
+    }
+
+    // Evaluates the formula regardless of whether the cached value is current, and caches the
+    // result.
+    fn recompute(&self, reactor: &Reactor<'r, T>) -> Option<T> {
+        let nv = self.evaluate(&|c| reactor.value(c), true)?;
+        reactor.recomputes.set(reactor.recomputes.get() + 1);
+        self.dirty.set(false);
+        self.update(reactor, nv.clone());
+        Some(nv)
+    }
+
+    // Recomputes the value from the cached values of the dependencies, which must already be up
+    // to date. Returns whether the value changed. A cell whose function fails, or that depends on
+    // such a cell, is left without a value, which counts as a change if it had one.
+    fn refresh(&self, reactor: &Reactor<'r, T>) -> bool {
+        let nv = self.evaluate(&|c| reactor.cached_value(c), true);
+        self.dirty.set(false);
+        match nv {
+            Some(nv) => {
+                reactor.recomputes.set(reactor.recomputes.get() + 1);
+                self.update(reactor, nv)
+            }
+            None => {
+                let old = self.prev_val.borrow_mut().take();
+                let lost = old.is_some();
+                if lost && reactor.batching {
+                    self.batched.borrow_mut().get_or_insert(old);
+                }
+                if lost {
+                    self.version.set(self.version.get() + 1);
+                }
+                lost
+            }
+        }
+    }
+
+    // Refreshes the cell, unless a read during propagation, e.g. by a callback, has already
+    // brought it up to date, and returns whether its value changed since its version was
+    // `version`.
+    fn refresh_since(&self, reactor: &Reactor<'r, T>, version: u64) -> bool {
+        if self.dirty.get() {
+            self.refresh(reactor);
+        }
+        self.version.get() != version
+    }
+
+    // Applies the formula to the values `read` yields for the cells it depends on, or returns
+    // None if a static dependency is missing. When `track` is set, the cells a dynamic formula
+    // read are kept as its pending dependencies, and a fold's state advances to the result.
+    fn evaluate(&self, read: &dyn Fn(CellID) -> Option<T>, track: bool) -> Option<T> {
+        let (nv, reads) = self.fun.apply(&self.deps, read)?;
+        if track {
+            self.fun.commit(&nv);
+            if reads.is_some() {
+                *self.pending_deps.borrow_mut() = reads;
+            }
+        }
+        Some(nv)
+    }
+
+    // Returns whether `a` and `b` count as the same value, using the cell's predicate if it has
+    // one.
+    fn same(&self, a: &T, b: &T) -> bool {
+        match &self.eq {
+            Some(eq) => eq(a, b),
+            None => a == b,
+        }
+    }
+
+    // Caches `nv` and fires the callbacks if it differs from the previous value. Returns whether
+    // the value changed. A value the cell's predicate deems the same isn't cached, so changes too
+    // small to count can't add up unnoticed.
+    fn update(&self, reactor: &Reactor<'r, T>, nv: T) -> bool {
+        let mut fire_callbacks = false;
+
+        let mut prev_val = self.prev_val.borrow_mut();
+        let unchanged = matches!(&*prev_val, Some(pv) if self.same(pv, &nv));
+        if reactor.batching && !unchanged {
+            self.batched
+                .borrow_mut()
+                .get_or_insert_with(|| prev_val.clone());
+        }
+        if prev_val.is_some() {
+            if !unchanged {
+                *prev_val = Some(nv.clone());
+                let seq = reactor.change_seq.get() + 1;
+                reactor.change_seq.set(seq);
+                self.changed_at.set(seq);
+                fire_callbacks = true;
+            }
+        } else {
+            *prev_val = Some(nv.clone());
+            fire_callbacks = true;
+        }
+        drop(prev_val);
+
+        if fire_callbacks {
+            self.history
+                .borrow_mut()
+                .record(reactor.generation, nv.clone());
+            self.version.set(self.version.get() + 1);
+        }
+
+        if fire_callbacks && !reactor.callbacks_muted && !reactor.batching {
+            self.fire(reactor, &nv);
+        }
+
+        fire_callbacks
+    }
+
+    // Fires the callbacks with the cell's new value.
+    fn fire(&self, reactor: &Reactor<'r, T>, nv: &T) {
+        // callbacks that are done are dropped once they've all fired, since the map can't
+        // change while we're iterating over it
+        let mut expired = Vec::new();
+        let callbacks = self.callbacks.borrow();
+        let was_firing = reactor.firing.replace(true);
+        for id in self.callback_order() {
+            let c = &callbacks[&id];
+            if c.enabled && !(*c.fun.borrow_mut())(nv, reactor) {
+                expired.push(id);
+            }
+        }
+        reactor.firing.set(was_firing);
+        drop(callbacks);
+        let mut callbacks = self.callbacks.borrow_mut();
+        for id in expired {
+            callbacks.remove(&id);
+        }
+    }
+
+    // Returns the callbacks in the order they fire: highest priority first, and in the order they
+    // were added among equal priorities.
+    fn callback_order(&self) -> Vec<CallbackID> {
+        let callbacks = self.callbacks.borrow();
+        let mut ids = callbacks.keys().copied().collect::<Vec<_>>();
+        ids.sort_by_key(|id| (Reverse(callbacks[id].priority), *id));
+        ids
+    }
+}
+
+/// `ReactorHandle` is a shared handle to a reactor whose whole graph can be replaced at once.
+/// Every clone of a handle sees the same reactor, and since a swap happens in a single step,
+/// readers only ever see the old graph or the new one, never a partly built one.
+///
+/// IDs, including `CallbackID`s, belong to the reactor that issued them: after a swap, IDs from
+/// the old reactor are meaningless and must not be used with the new one.
+pub struct ReactorHandle<'r, T: Debug> {
+    inner: Rc<RefCell<Reactor<'r, T>>>,
+}
+
+impl<'r, T: Debug> Clone for ReactorHandle<'r, T> {
+    fn clone(&self) -> Self {
+        ReactorHandle {
+            inner: Rc::clone(&self.inner),
+        }
+    }
+}
+
+impl<'r, T: Debug> ReactorHandle<'r, T> {
+    pub fn new(reactor: Reactor<'r, T>) -> Self {
+        ReactorHandle {
+            inner: Rc::new(RefCell::new(reactor)),
+        }
+    }
+
+    // Replaces the reactor behind every clone of this handle, returning the old one.
+    //
+    // Panics if the reactor is currently borrowed through this handle.
+    pub fn swap(&self, new: Reactor<'r, T>) -> Reactor<'r, T> {
+        self.inner.replace(new)
+    }
+
+    pub fn borrow(&self) -> Ref<'_, Reactor<'r, T>> {
+        self.inner.borrow()
+    }
+
+    pub fn borrow_mut(&self) -> RefMut<'_, Reactor<'r, T>> {
+        self.inner.borrow_mut()
+    }
+}
+
+/// `Propagation` is an input change, made by `Reactor::begin_set`, that is propagated through the
+/// graph one layer at a time. A layer is every pending compute cell of the lowest rank (see
+/// `Reactor::cell_ranks`), so a cell is only recomputed once all of its dependencies are final.
+///
+/// The graph isn't settled until every layer has been stepped. If the `Propagation` is dropped
+/// early, the cells it didn't reach are recomputed, and their callbacks fire, when they're next
+/// read or the next change is propagated, whichever comes first. Global callbacks fire once the
+/// last layer has been stepped, so not at all for a `Propagation` dropped early.
+pub struct Propagation<'a, 'r, T: Debug> {
+    reactor: &'a mut Reactor<'r, T>,
+    pending: BTreeSet<(usize, ComputeCellID)>,
+    marked: Vec<ComputeCellID>,
+    versions: Vec<u64>,          // every compute cell's version before the change
+    changed: Vec<ComputeCellID>, // for the global callbacks, in the order they changed
+}
+
+impl<'a, 'r, T: Clone + Debug + PartialEq + 'r> Propagation<'a, 'r, T> {
+    // Recomputes the next layer, firing callbacks as `set_value` would, and returns the cells in
+    // it whose value changed along with their new values. Returns None once there is nothing
+    // left to recompute.
+    pub fn step_layer(&mut self) -> Option<Vec<(ComputeCellID, T)>> {
+        let (rank, _) = *self.pending.first()?;
+        let reactor = &*self.reactor;
+        let mut changes = Vec::new();
+        while let Some(&(r, id @ ComputeCellID(idx))) = self.pending.first() {
+            if r != rank {
+                break;
+            }
+            self.pending.pop_first();
+            let cell = &reactor.compute_cells[idx];
+            if cell.refresh_since(reactor, self.versions[idx]) {
+                self.changed.push(id);
+                self.pending.extend(
+                    cell.clients
+                        .iter()
+                        .map(|c @ ComputeCellID(cidx)| (reactor.compute_cells[*cidx].rank, *c)),
+                );
+                if let Some(value) = cell.cached() {
+                    changes.push((id, value));
+                }
+            }
+        }
+        if self.pending.is_empty() {
+            let marked = std::mem::take(&mut self.marked);
+            self.reactor.settle(marked);
+            let changed = std::mem::take(&mut self.changed);
+            self.reactor.fire_global(changed);
+        }
+        Some(changes)
+    }
+}
+
+/// How many levels of dependencies `Reactor::explain` expands before eliding the rest.
+pub const MAX_EXPLAIN_DEPTH: usize = 16;
+
+/// `PropagationProbe` tells whether its reactor is firing callbacks, as `Reactor::in_propagation`
+/// does. Callbacks can't reach the reactor while it fires them, so they can hold a probe instead
+/// and check it before deciding to defer a mutation.
+#[derive(Clone, Debug)]
+pub struct PropagationProbe {
+    firing: Rc<Cell<bool>>,
+}
+
+impl PropagationProbe {
+    pub fn in_propagation(&self) -> bool {
+        self.firing.get()
+    }
+}
+
+/// `Subscription` is a callback registered by `Reactor::subscribe`, which lasts only as long as
+/// the subscription does. The guard can't reach the reactor, so dropping it doesn't remove the
+/// callback right away: it stops it from firing, and the reactor drops it the next time the cell
+/// changes. `unsubscribe` removes it at once.
+#[derive(Debug)]
+#[must_use = "dropping a Subscription cancels its callback"]
+pub struct Subscription {
+    cell: ComputeCellID,
+    id: CallbackID,
+    live: Rc<Cell<bool>>,
+}
+
+impl Subscription {
+    pub fn cell(&self) -> ComputeCellID {
+        self.cell
+    }
+
+    pub fn id(&self) -> CallbackID {
+        self.id
+    }
+
+    // Removes the callback from `reactor`, which must be the reactor that issued the
+    // subscription.
+    pub fn unsubscribe<'r, T: Clone + Debug + PartialEq + 'r>(self, reactor: &mut Reactor<'r, T>) {
+        // the callback might already be gone, e.g. with its cell
+        let _ = reactor.remove_callback(self.cell, self.id);
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        self.live.set(false);
+    }
+}
+
+/// The most paths `Reactor::all_paths` will return.
+pub const MAX_PATHS: usize = 1024;
+
+/// How deeply reads of compute cells may nest, e.g. through callbacks that read other cells as
+/// they fire, or dynamic cells reading cells they hadn't before. Past this depth `Reactor::value`
+/// gives up and returns None rather than overflowing the stack. A stale chain of dependencies,
+/// however long, doesn't count, since it's brought up to date one cell at a time.
+pub const MAX_EVAL_DEPTH: usize = 512;
+
+#[derive(Default)]
+pub struct Reactor<'r, T: Debug> {
+    input_cells: Vec<InputCell<'r, T>>,
+    compute_cells: Vec<ComputeCell<'r, T>>,
+    callbacks_muted: bool,
+    lazy: bool,     // whether setting inputs defers recomputation to the next read
+    batching: bool, // whether callbacks are held back until `end_batch`
+    staged: HashMap<InputCellID, T>,
+    change_seq: Cell<u64>,   // bumped every time a compute cell's value changes
+    eval_depth: Cell<usize>, // how many calls to `value` are on the stack
+    recomputes: Cell<u64>,   // how many times a compute cell's formula ran to update its value
+    firing: Rc<Cell<bool>>,  // whether callbacks are being fired; shared with `PropagationProbe`s
+    generation: u64,         // bumped every time a call changes the value of any input
+    keys: HashMap<String, CellID>,
+    cell_keys: HashMap<CellID, String>,
+    futures: Rc<RefCell<Vec<BoxedFuture<'r>>>>, // returned by async callbacks, not yet taken
+    drained: HashMap<CellID, u64>, // each cell's `generation` as of the last `drain_changes`
+    global_callbacks: Vec<GlobalCallback<'r, T>>,
+}
+
+// What `Reactor`'s Debug output shows of a compute cell: everything but its function.
+struct ComputeSummary<'a, T> {
+    id: ComputeCellID,
+    deps: &'a [CellID],
+    callbacks: usize,
+    value: Option<T>,
+}
+
+impl<'a, T: Debug> Debug for ComputeSummary<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ComputeCell")
+            .field("id", &self.id)
+            .field("deps", &self.deps)
+            .field("callbacks", &self.callbacks)
+            .field("value", &self.value)
+            .finish()
+    }
+}
+
+impl<'r, T: Clone + Debug + PartialEq + 'r> Debug for Reactor<'r, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let inputs = self
+            .input_cells
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| !c.removed)
+            .map(|(idx, c)| (InputCellID(idx), &c.value))
+            .collect::<BTreeMap<_, _>>();
+        let computes = self
+            .compute_cells
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| !c.removed)
+            .map(|(idx, c)| ComputeSummary {
+                id: ComputeCellID(idx),
+                deps: &c.deps,
+                callbacks: c.callbacks.borrow().len(),
+                value: c.cached(),
+            })
+            .collect::<Vec<_>>();
+        f.debug_struct("Reactor")
+            .field("inputs", &inputs)
+            .field("computes", &computes)
+            .finish()
+    }
+}
+
+/// Cloning a reactor copies every cell, with its value and dependencies, but none of its
+/// callbacks, whether per-cell or global, which can't be cloned: the clone starts out with no
+/// callbacks at all, so it can be changed speculatively without side effects. Nor does it get
+/// any futures not yet taken. Compute functions are shared between the two.
+impl<'r, T: Clone + Debug + PartialEq + 'r> Clone for Reactor<'r, T> {
+    fn clone(&self) -> Self {
+        Reactor {
+            input_cells: self
+                .input_cells
+                .iter()
+                .map(InputCell::without_callbacks)
+                .collect(),
+            compute_cells: self
+                .compute_cells
+                .iter()
+                .map(ComputeCell::without_callbacks)
+                .collect(),
+            callbacks_muted: self.callbacks_muted,
+            lazy: self.lazy,
+            batching: self.batching,
+            staged: self.staged.clone(),
+            change_seq: self.change_seq.clone(),
+            eval_depth: Cell::new(0),
+            recomputes: self.recomputes.clone(),
+            firing: Rc::new(Cell::new(false)),
+            generation: self.generation,
+            keys: self.keys.clone(),
+            cell_keys: self.cell_keys.clone(),
+            futures: Rc::new(RefCell::new(Vec::new())),
+            drained: self.drained.clone(),
+            global_callbacks: Vec::new(),
+        }
+    }
+}
+
+// You are guaranteed that Reactor will only be tested against types that are Clone + PartialEq.
+impl<'r, T: Clone + Debug + PartialEq + 'r> Reactor<'r, T> {
+    pub fn new() -> Self {
+        Reactor {
+            input_cells: Vec::new(),
+            compute_cells: Vec::new(),
+            callbacks_muted: false,
+            lazy: false,
+            batching: false,
+            staged: HashMap::new(),
+            change_seq: Cell::new(0),
+            eval_depth: Cell::new(0),
+            recomputes: Cell::new(0),
+            firing: Rc::new(Cell::new(false)),
+            generation: 0,
+            keys: HashMap::new(),
+            cell_keys: HashMap::new(),
+            futures: Rc::new(RefCell::new(Vec::new())),
+            drained: HashMap::new(),
+            global_callbacks: Vec::new(),
+        }
+    }
+
+    // Creates an input cell with the specified initial value, returning its ID.
+    pub fn create_input(&mut self, initial: T) -> InputCellID {
+        let idx = self.input_cells.len();
+        let id = InputCellID(idx);
+        self.input_cells
+            .push(InputCell::new(initial, self.generation));
+        id
+    }
+
+    // Creates a compute cell with the specified dependencies and compute function.
+    // The compute function is expected to take in its arguments in the same order as specified in
+    // `dependencies`.
+    // You do not need to reject compute functions that expect more arguments than there are
+    // dependencies (how would you check for this, anyway?).
+    //
+    // If any dependency doesn't exist, returns `Err(NonexistentDependency)` with that nonexistent
+    // dependency. (If multiple dependencies do not exist, exactly which one is returned is not
+    // defined and will not be tested)
+    //
+    // If a dependency depends, transitively, on the cell being created, returns `Err(Cycle)` with
+    // that dependency rather than creating a cycle.
+    //
+    // A cell can only be removed once nothing depends on it (see `remove_input`), so you may
+    // assume, without checking, that if the dependencies exist at creation time they will continue
+    // to exist as long as this cell does.
+    pub fn create_compute<F>(
+        &mut self,
+        dependencies: &[CellID],
+        compute_func: F,
+    ) -> Result<ComputeCellID, CreateComputeError>
+    where
+        F: 'r + Fn(&[T]) -> T,
+    {
+        self.insert_compute(dependencies, Formula::Static(Rc::new(compute_func)))
+    }
+
+    // Creates a compute cell as `create_compute` does, but which uses `eq` rather than `==` to
+    // decide whether its value changed, e.g. to ignore differences below some tolerance. A new
+    // value `eq` deems the same as the old one is discarded: the cell keeps the old value, and
+    // neither fires its callbacks nor propagates.
+    //
+    // Returns an Err if the cell can't be created, as `create_compute` does.
+    pub fn create_compute_with_eq<F, E>(
+        &mut self,
+        dependencies: &[CellID],
+        compute_func: F,
+        eq: E,
+    ) -> Result<ComputeCellID, CreateComputeError>
+    where
+        F: 'r + Fn(&[T]) -> T,
+        E: 'r + Fn(&T, &T) -> bool,
+    {
+        let id @ ComputeCellID(idx) = self.create_compute(dependencies, compute_func)?;
+        self.compute_cells[idx].eq = Some(Rc::new(eq));
+        Ok(id)
+    }
+
+    // Creates a compute cell with one dependency, whose function takes the dependency's value
+    // directly rather than in a slice. Returns an Err as `create_compute` does.
+    pub fn map1<F>(
+        &mut self,
+        a: impl Into<CellID>,
+        f: F,
+    ) -> Result<ComputeCellID, CreateComputeError>
+    where
+        F: 'r + Fn(T) -> T,
+    {
+        self.create_compute(&[a.into()], move |v| f(v[0].clone()))
+    }
+
+    // As `map1`, for two dependencies, whose values are passed in the order given.
+    pub fn map2<F>(
+        &mut self,
+        a: impl Into<CellID>,
+        b: impl Into<CellID>,
+        f: F,
+    ) -> Result<ComputeCellID, CreateComputeError>
+    where
+        F: 'r + Fn(T, T) -> T,
+    {
+        self.create_compute(&[a.into(), b.into()], move |v| {
+            f(v[0].clone(), v[1].clone())
+        })
+    }
+
+    // As `map1`, for three dependencies, whose values are passed in the order given.
+    pub fn map3<F>(
+        &mut self,
+        a: impl Into<CellID>,
+        b: impl Into<CellID>,
+        c: impl Into<CellID>,
+        f: F,
+    ) -> Result<ComputeCellID, CreateComputeError>
+    where
+        F: 'r + Fn(T, T, T) -> T,
+    {
+        self.create_compute(&[a.into(), b.into(), c.into()], move |v| {
+            f(v[0].clone(), v[1].clone(), v[2].clone())
+        })
+    }
+
+    // Creates a compute cell that folds its dependencies' values into an accumulated state, such
+    // as a running sum or a running maximum. Each time the cell is recomputed, `step` is handed
+    // the current state and the dependencies' values, and returns the new state, which is also
+    // the cell's value. It's first applied to `initial` when the cell is created.
+    //
+    // Since the value depends on how many times the cell has been recomputed, and not only on
+    // its dependencies' current values, caching matters. The fold steps exactly when the cached
+    // value is brought up to date: once per change that reaches the cell when eager, but only once
+    // per read after any number of changes when lazy (see `set_eager`), and on every read with
+    // `CachePolicy::Session`, which propagates the new value as a change would. Scratch
+    // evaluations, such as `CachePolicy::Never`, give the current value while the dependencies'
+    // values are the ones last folded in, and otherwise compute the next step without taking it.
+    //
+    // Returns an Err as `create_compute` does.
+    pub fn create_fold<F>(
+        &mut self,
+        dependencies: &[CellID],
+        initial: T,
+        step: F,
+    ) -> Result<ComputeCellID, CreateComputeError>
+    where
+        F: 'r + FnMut(&T, &[T]) -> T,
+    {
+        let step: FoldFn<'r, T> = Rc::new(RefCell::new(step));
+        let state = Rc::new(RefCell::new(initial));
+        self.insert_compute(dependencies, Formula::Fold(step, state))
+    }
+
+    // Creates a compute cell as `create_compute` does, but whose function can fail. While it's
+    // failing, the cell has no value: `value` returns None for it and for every cell depending on
+    // it, and `try_value` returns the error. Its callbacks don't fire on failing, and fire with
+    // the new value once it recovers.
+    pub fn create_fallible_compute<F, E>(
+        &mut self,
+        dependencies: &[CellID],
+        compute_func: F,
+    ) -> Result<ComputeCellID, CreateComputeError>
+    where
+        F: 'r + Fn(&[T]) -> Result<T, E>,
+        E: Display,
+    {
+        let fun = move |v: &[T]| compute_func(v).map_err(|e| e.to_string());
+        self.insert_compute(dependencies, Formula::Fallible(Rc::new(fun)))
+    }
+
+    fn insert_compute(
+        &mut self,
+        dependencies: &[CellID],
+        fun: Formula<'r, T>,
+    ) -> Result<ComputeCellID, CreateComputeError> {
+        let cid = ComputeCellID(self.compute_cells.len());
+        self.define_compute(cid, dependencies, fun)?;
+        Ok(cid)
+    }
+
+    // Builds the compute cell `cid`, either the next new one or one whose slot is held by a
+    // placeholder, and evaluates it.
+    fn define_compute(
+        &mut self,
+        cid: ComputeCellID,
+        dependencies: &[CellID],
+        fun: Formula<'r, T>,
+    ) -> Result<(), CreateComputeError> {
+        let ComputeCellID(cidx) = cid;
+
+        if let Some(id) = dependencies.iter().find(|id| !self.contains(**id)) {
+            return Err(CreateComputeError::NonexistentDependency(*id));
+        }
+
+        // nothing can depend on a cell that doesn't exist yet, so this can't currently fail, but
+        // it keeps recomputation from recursing forever should that ever change
+        if let Some(dep) = self.path_to(dependencies, CellID::Compute(cid)) {
+            return Err(CreateComputeError::Cycle(dep));
+        }
+
+        // register as clients with all dependencies.
+        for id in dependencies.iter() {
+            match id {
+                CellID::Input(InputCellID(idx)) => {
+                    let _ = self.input_cells[*idx].clients.insert(cid);
+                }
+                CellID::Compute(ComputeCellID(idx)) => {
+                    let _ = self.compute_cells[*idx].clients.insert(cid);
+                }
+            }
+        }
+        let mut cell = ComputeCell::with_formula(fun, dependencies);
+        cell.rank = self.rank_above(dependencies);
+        cell.call(self); // set the initial value
+        cell.version.set(0);
+        if cidx == self.compute_cells.len() {
+            self.compute_cells.push(cell);
+        } else {
+            self.compute_cells[cidx] = cell;
+        }
+
+        Ok(())
+    }
+
+    // Creates a compute cell as `create_compute` does and adds `callback` to it. The callback
+    // first fires on the first change after creation, not for the initial value.
+    //
+    // Returns an Err if the cell can't be created, as `create_compute` does.
+    pub fn create_compute_watched<F, C>(
+        &mut self,
+        dependencies: &[CellID],
+        compute_func: F,
+        callback: C,
+    ) -> Result<(ComputeCellID, CallbackID), CreateComputeError>
+    where
+        F: 'r + Fn(&[T]) -> T,
+        C: 'r + FnMut(T),
+    {
+        let id = self.create_compute(dependencies, compute_func)?;
+        let cbid = self.add_callback(id, callback).unwrap();
+        Ok((id, cbid))
+    }
+
+    // Creates a compute cell whose dependencies are whichever cells `compute_func` reads through
+    // its `DynCtx`, rather than a fixed list. The set is re-discovered on every evaluation, so a
+    // cell that picks between branches only depends on the branch it last picked.
+    //
+    // A dynamic cell can only read cells created before it; reads of any other cell yield None.
+    pub fn create_dynamic<F>(&mut self, compute_func: F) -> ComputeCellID
+    where
+        F: 'r + Fn(&DynCtx<'_, T>) -> T,
+    {
+        let cidx = self.compute_cells.len();
+        let mut cell =
+            ComputeCell::with_formula(Formula::Dynamic(Rc::new(compute_func), cidx), &[]);
+        cell.rank = self.rank_above(&[]);
+        cell.call(self); // set the initial value and dependencies
+        cell.version.set(0);
+        self.compute_cells.push(cell);
+        self.sync_dependencies();
+        ComputeCellID(cidx)
+    }
+
+    // Captures the input values and the graph structure; see `ReactorSnapshot`.
+    pub fn to_snapshot(&self) -> ReactorSnapshot<T> {
+        let inputs = self.input_cells.iter().enumerate();
+        let computes = self.compute_cells.iter().enumerate();
+        ReactorSnapshot {
+            inputs: self.input_cells.iter().map(|c| c.value.clone()).collect(),
+            computes: self.compute_cells.iter().map(|c| c.deps.clone()).collect(),
+            removed: inputs
+                .filter(|(_, c)| c.removed)
+                .map(|(idx, _)| CellID::Input(InputCellID(idx)))
+                .chain(
+                    computes
+                        .filter(|(_, c)| c.removed)
+                        .map(|(idx, _)| CellID::Compute(ComputeCellID(idx))),
+                )
+                .collect(),
+        }
+    }
+
+    // Rebuilds a reactor from a snapshot, calling `rebuild` for the compute function of each
+    // compute cell that wasn't removed, in ID order. Cells get the same IDs they had in the
+    // snapshotted reactor, so given the same functions, every cell has the same value it had
+    // there. Cells are evaluated dependencies first, which since `set_dependencies` needn't be
+    // ID order.
+    //
+    // If a compute cell can't be created, e.g. because a dependency doesn't exist, as can happen
+    // with a snapshot that didn't come from `to_snapshot`, returns the error creating it. If the
+    // cells' dependencies form a cycle, returns `Err(Cycle)` with one of the cells on it.
+    pub fn from_snapshot<F>(
+        snapshot: &ReactorSnapshot<T>,
+        mut rebuild: F,
+    ) -> Result<Self, CreateComputeError>
+    where
+        F: FnMut(ComputeCellID) -> BoxedComputeFn<'r, T>,
+    {
+        let mut reactor = Reactor::new();
+        for value in snapshot.inputs.iter() {
+            reactor.create_input(value.clone());
+        }
+        // every slot starts out as a placeholder that's never evaluated, so each cell can be
+        // defined in place once its dependencies have been, whatever their IDs
+        let mut pending = Vec::new();
+        for idx in 0..snapshot.computes.len() {
+            let id = ComputeCellID(idx);
+            let mut cell = ComputeCell::new(|_| unreachable!(), &[]);
+            cell.removed = true;
+            reactor.compute_cells.push(cell);
+            if !snapshot.removed.contains(&CellID::Compute(id)) {
+                pending.push((id, Formula::Static(Rc::from(rebuild(id)))));
+            }
+        }
+        let pending_dep = |pending: &[(ComputeCellID, Formula<'r, T>)], ComputeCellID(idx)| {
+            snapshot.computes[idx].iter().find_map(|d| match d {
+                CellID::Compute(dep) if pending.iter().any(|(p, _)| p == dep) => Some(*dep),
+                _ => None,
+            })
+        };
+        while !pending.is_empty() {
+            let ready = match pending
+                .iter()
+                .position(|(id, _)| pending_dep(&pending, *id).is_none())
+            {
+                Some(ready) => ready,
+                None => {
+                    // every cell left waits on another, so following them from any one of them
+                    // comes back around to a cell on a cycle
+                    let mut seen = HashSet::new();
+                    let mut id = pending[0].0;
+                    while seen.insert(id) {
+                        id = pending_dep(&pending, id).unwrap();
+                    }
+                    return Err(CreateComputeError::Cycle(CellID::Compute(id)));
+                }
+            };
+            let (id @ ComputeCellID(idx), fun) = pending.remove(ready);
+            reactor.define_compute(id, &snapshot.computes[idx], fun)?;
+        }
+        for id in snapshot.removed.iter() {
+            if let CellID::Input(InputCellID(idx)) = *id {
+                if let Some(cell) = reactor.input_cells.get_mut(idx) {
+                    cell.removed = true;
+                }
+            }
+        }
+        Ok(reactor)
+    }
+
+    // Moves every cell of `other` into this reactor, after its own, along with their callbacks,
+    // keys and staged values, and `other`'s global callbacks. Returns the mapping from `other`'s
+    // IDs to the cells' new IDs.
+    //
+    // Values carry over as they are, so nothing fires, except that if `other` was mid-batch,
+    // the batch ends first, and if it was lazy and this reactor isn't, its stale cells are brought
+    // up to date first, as reading them would. History starts afresh at this reactor's current
+    // generation, and a key that this reactor already uses stays with its own cell, leaving the
+    // merged cell without one.
+    //
+    // A dynamic cell reads whichever IDs its function names, which can't be remapped, so it
+    // should only be merged if it reads cells through IDs translated with the returned remap.
+    pub fn merge(&mut self, mut other: Reactor<'r, T>) -> IdRemap {
+        other.end_batch();
+        if !self.lazy {
+            other.catch_up();
+        }
+        other.sync_dependencies();
+        let remap = IdRemap {
+            inputs: self.input_cells.len(),
+            computes: self.compute_cells.len(),
+        };
+        let remap_clients = |clients: &mut HashSet<ComputeCellID>| {
+            *clients = clients.iter().map(|c| remap.compute(*c)).collect();
+        };
+
+        for mut cell in std::mem::take(&mut other.input_cells) {
+            remap_clients(&mut cell.clients);
+            cell.history = History::new();
+            cell.history.record(self.generation, cell.value.clone());
+            self.input_cells.push(cell);
+        }
+        let seq = self.change_seq.get();
+        for mut cell in std::mem::take(&mut other.compute_cells) {
+            cell.deps = cell.deps.iter().map(|d| remap.cell(*d)).collect();
+            if let Some(deps) = cell.pending_deps.get_mut() {
+                *deps = deps.iter().map(|d| remap.cell(*d)).collect();
+            }
+            remap_clients(&mut cell.clients);
+            if let Formula::Dynamic(_, idx) = &mut cell.fun {
+                *idx += remap.computes;
+            }
+            if cell.changed_at.get() > 0 {
+                cell.changed_at.set(cell.changed_at.get() + seq);
+            }
+            let mut history = History::new();
+            if let Some(v) = cell.cached() {
+                history.record(self.generation, v);
+            }
+            *cell.history.get_mut() = history;
+            self.compute_cells.push(cell);
+        }
+        self.change_seq.set(seq + other.change_seq.get());
+
+        for (id, value) in other.staged.drain() {
+            self.staged.insert(remap.input(id), value);
+        }
+        for (id, generation) in other.drained.drain() {
+            self.drained.insert(remap.cell(id), generation);
+        }
+        for (key, id) in other.keys.drain() {
+            if !self.keys.contains_key(&key) {
+                self.cell_keys.insert(remap.cell(id), key.clone());
+                self.keys.insert(key, remap.cell(id));
+            }
+        }
+        self.futures
+            .borrow_mut()
+            .extend(other.futures.borrow_mut().drain(..));
+        self.global_callbacks.append(&mut other.global_callbacks);
+        remap
+    }
+
+    // Creates a compute cell with no dependencies that always holds `value`. Unlike an input
+    // cell, its value can never be changed.
+    pub fn create_constant(&mut self, value: T) -> ComputeCellID {
+        match self.create_compute(&[], move |_| value.clone()) {
+            Ok(id) => id,
+            Err(_) => unreachable!("a cell without dependencies can't be missing any"),
+        }
+    }
+
+    // Removes an input cell. Its ID is never reused, and from now on behaves as though the cell
+    // never existed.
+    //
+    // Returns an Err if the cell doesn't exist, or if any compute cell still depends on it.
+    pub fn remove_input(&mut self, id: InputCellID) -> Result<(), RemoveCellError> {
+        self.sync_dependencies();
+        self.check_removable(CellID::Input(id))?;
+        let InputCellID(idx) = id;
+        let cell = &mut self.input_cells[idx];
+        cell.removed = true;
+        cell.history = History::new();
+        cell.callbacks.clear();
+        self.staged.remove(&id);
+        self.remove_key(CellID::Input(id));
+        Ok(())
+    }
+
+    // Replaces a compute cell's function, keeping its dependencies, then recomputes the cell. If
+    // its value changed, its callbacks fire and the change propagates as it would from
+    // `set_value`; if not, nothing fires.
+    //
+    // Returns an Err with the cell's ID if it doesn't exist.
+    pub fn set_compute_fn<F>(&mut self, id: ComputeCellID, f: F) -> Result<(), CellID>
+    where
+        F: 'r + Fn(&[T]) -> T,
+    {
+        if !self.contains(CellID::Compute(id)) {
+            return Err(CellID::Compute(id));
+        }
+        let ComputeCellID(idx) = id;
+        self.compute_cells[idx].fun = Formula::Static(Rc::new(f));
+        self.recompute_edited(id);
+        Ok(())
+    }
+
+    // Replaces a compute cell's dependencies, keeping its function, which from now on is handed
+    // the new dependencies' values in the order given. The cell is recomputed, and if its value
+    // changed, its callbacks fire and the change propagates as it would from `set_value`.
+    //
+    // A dynamic cell's dependencies are whichever cells it reads, so rewiring one only lasts
+    // until it's next evaluated.
+    //
+    // Returns an Err with the cell's ID if it doesn't exist, with a new dependency that doesn't
+    // exist, or with a new dependency that depends, transitively, on the cell, since that would
+    // create a cycle. The cell is left as it was.
+    pub fn set_dependencies(&mut self, id: ComputeCellID, deps: &[CellID]) -> Result<(), CellID> {
+        if !self.contains(CellID::Compute(id)) {
+            return Err(CellID::Compute(id));
+        }
+        self.sync_dependencies();
+        if let Some(dep) = deps.iter().find(|d| !self.contains(**d)) {
+            return Err(*dep);
+        }
+        if let Some(dep) = self.path_to(deps, CellID::Compute(id)) {
+            return Err(dep);
+        }
+        let ComputeCellID(idx) = id;
+        for d in std::mem::replace(&mut self.compute_cells[idx].deps, deps.to_vec()) {
+            self.clients_mut(d).remove(&id);
+        }
+        for d in deps {
+            self.clients_mut(*d).insert(id);
+        }
+        self.rerank();
+        self.recompute_edited(id);
+        Ok(())
+    }
+
+    // Removes a compute cell, along with its callbacks. Its ID is never reused, and from now on
+    // behaves as though the cell never existed.
+    //
+    // Returns an Err if the cell doesn't exist, or if any other compute cell still depends on it.
+    pub fn remove_compute(&mut self, id: ComputeCellID) -> Result<(), RemoveCellError> {
+        self.sync_dependencies();
+        self.check_removable(CellID::Compute(id))?;
+        let ComputeCellID(idx) = id;
+        for d in std::mem::take(&mut self.compute_cells[idx].deps) {
+            self.clients_mut(d).remove(&id);
+        }
+        let cell = &mut self.compute_cells[idx];
+        cell.removed = true;
+        cell.callbacks.get_mut().clear();
+        *cell.history.get_mut() = History::new();
+        self.remove_key(CellID::Compute(id));
+        Ok(())
+    }
+
+    fn check_removable(&self, id: CellID) -> Result<(), RemoveCellError> {
+        if !self.contains(id) {
+            return Err(RemoveCellError::NonexistentCell);
+        }
+        let mut dependents = self
+            .clients(id)
+            .iter()
+            .map(|c| CellID::Compute(*c))
+            .collect::<Vec<_>>();
+        if dependents.is_empty() {
+            return Ok(());
+        }
+        dependents.sort();
+        Err(RemoveCellError::HasDependents(dependents))
+    }
+
+    // Retrieves the current value of the cell, or None if the cell does not exist.
+    //
+    // Compute cells are only recomputed if an input upstream of them has changed since they were
+    // last computed; otherwise their cached value is returned.
+    //
+    // You may wonder whether it is possible to implement `get(&self, id: CellID) -> Option<&Cell>`
+    // and have a `value(&self)` method on `Cell`.
+    //
+    // It turns out this introduces a significant amount of extra complexity to this exercise.
+    // We chose not to cover this here, since this exercise is probably enough work as-is.
+    pub fn value(&self, id: CellID) -> Option<T> {
+        match id {
+            CellID::Input(InputCellID(idx)) => {
+                self.input_cell(InputCellID(idx)).map(|i| i.value.clone())
+            }
+            CellID::Compute(ComputeCellID(idx)) => {
+                let cell = self.compute_cell(ComputeCellID(idx))?;
+                let depth = self.eval_depth.get();
+                if depth >= MAX_EVAL_DEPTH {
+                    return None;
+                }
+                self.eval_depth.set(depth + 1);
+                if cell.dirty.get() {
+                    self.catch_up_to(ComputeCellID(idx));
+                }
+                let v = cell.call(self);
+                self.eval_depth.set(depth);
+                v
+            }
+        }
+    }
+
+    // Retrieves the value of the cell as `value` does, or the reason a compute cell has no value:
+    // its fallible function failed, or a cell it depends on has no value.
+    //
+    // Returns None if the cell does not exist.
+    pub fn try_value(&self, id: CellID) -> Option<Result<T, ComputeError>> {
+        if !self.contains(id) {
+            return None;
+        }
+        if let Some(v) = self.value(id) {
+            return Some(Ok(v));
+        }
+        let cell = match id {
+            CellID::Compute(ComputeCellID(idx)) => &self.compute_cells[idx],
+            CellID::Input(_) => unreachable!("input cells always have a value"),
+        };
+        for d in cell.deps.iter() {
+            if self.value(*d).is_none() {
+                return Some(Err(match *d {
+                    CellID::Compute(dep) => ComputeError::FailedDependency(dep),
+                    CellID::Input(_) => ComputeError::MissingDependency(*d),
+                }));
+            }
+        }
+        // with no error reported, the cell was too deeply nested to evaluate; see `MAX_EVAL_DEPTH`
+        let msg = cell.fun.error(&cell.deps, &|c| self.value(c));
+        Some(Err(ComputeError::Failed(msg.unwrap_or_default())))
+    }
+
+    // Evaluates the cell from scratch, as `value_with_policy` with `CachePolicy::Never` does, and
+    // also returns the trace of the evaluation: each cell it read, paired with its value, in the
+    // order their values were settled. Every cell appears once, after the cells it depends on,
+    // and the last entry is the cell itself.
+    //
+    // Returns None if the cell does not exist.
+    pub fn value_traced(&self, id: CellID) -> Option<(T, Vec<(CellID, T)>)> {
+        let mut scratch = Scratch::new();
+        scratch.trace = Some(Vec::new());
+        let v = self.eval_scratch(id, &mut scratch)?;
+        Some((v, scratch.trace.unwrap_or_default()))
+    }
+
+    // Returns how many times compute cells' functions have run to bring their values up to date,
+    // since the reactor was created or the count was last reset. Scratch evaluations, such as
+    // `value_with_policy` with `CachePolicy::Never`, aren't counted.
+    pub fn recompute_count(&self) -> u64 {
+        self.recomputes.get()
+    }
+
+    // Resets the count `recompute_count` returns to zero.
+    pub fn reset_recompute_count(&mut self) {
+        self.recomputes.set(0);
+    }
+
+    // Retrieves the value of the cell without evaluating anything: an input cell's value, or a
+    // compute cell's cached value, which is current unless an evaluation was cut short (see
+    // `MAX_EVAL_DEPTH`). Same as `value_with_policy` with `CachePolicy::Always`.
+    //
+    // Returns None if the cell does not exist or has never been evaluated.
+    pub fn peek_value(&self, id: CellID) -> Option<T> {
+        self.cached_value(id)
+    }
+
+    // Sets the value of the specified input cell.
+    //
+    // Returns false if the cell does not exist.
+    //
+    // Similarly, you may wonder about `get_mut(&mut self, id: CellID) -> Option<&mut Cell>`, with
+    // a `set_value(&mut self, new_value: T)` method on `Cell`.
+    //
+    // As before, that turned out to add too much extra complexity.
+    pub fn set_value(&mut self, id: InputCellID, new_value: T) -> bool {
+        if self.contains(CellID::Input(id)) {
+            self.apply_inputs(Some((id, new_value)));
+            true
+        } else {
+            false
+        }
+    }
+
+    // Sets several input cells at once, then propagates once, so each affected compute cell is
+    // recomputed, and its callbacks fired, at most once, with its final value. If the same cell
+    // appears more than once, the last value given for it wins.
+    //
+    // Returns false, setting nothing, if any of the cells does not exist.
+    pub fn set_values(&mut self, updates: &[(InputCellID, T)]) -> bool {
+        if updates
+            .iter()
+            .any(|(id, _)| !self.contains(CellID::Input(*id)))
+        {
+            return false;
+        }
+        self.apply_inputs(updates.iter().cloned());
+        true
+    }
+
+    // Adds a callback to the specified compute cell.
+    //
+    // Returns the ID of the just-added callback, or None if the cell doesn't exist.
+    //
+    // Callbacks on input cells are added with `add_input_callback`.
+    //
+    // The semantics of callbacks (as will be tested):
+    // For a single set_value call, each compute cell's callbacks should each be called:
+    // * Zero times if the compute cell's value did not change as a result of the set_value call.
+    // * Exactly once if the compute cell's value changed as a result of the set_value call.
+    //   The value passed to the callback should be the final value of the compute cell after the
+    //   set_value call.
+    pub fn add_callback<F: 'r + FnMut(T)>(
+        &mut self,
+        id: ComputeCellID,
+        mut callback: F,
+    ) -> Option<CallbackID> {
+        if !self.contains(CellID::Compute(id)) {
+            return None;
+        }
+
+        Some(self.insert_callback(id, move |v| {
+            callback(v.clone());
+            true
+        }))
+    }
+
+    // Adds a callback that is handed a reference to the cell's new value, rather than a clone of
+    // it as `add_callback` does, otherwise behaving the same.
+    //
+    // Returns the ID of the just-added callback, or None if the cell doesn't exist.
+    pub fn add_callback_ref<F: 'r + FnMut(&T)>(
+        &mut self,
+        id: ComputeCellID,
+        mut callback: F,
+    ) -> Option<CallbackID> {
+        if !self.contains(CellID::Compute(id)) {
+            return None;
+        }
+        Some(self.insert_callback(id, move |v| {
+            callback(v);
+            true
+        }))
+    }
+
+    // Adds a callback to the specified input cell. It follows the same semantics as callbacks on
+    // compute cells: for a single call that sets inputs, such as `set_value` or `set_values`, it's
+    // called zero times if the input's value did not change, and exactly once, with the final
+    // value, if it did. Input callbacks fire before any compute cell's. Remove one with
+    // `remove_input_callback`.
+    //
+    // Returns the ID of the just-added callback, or None if the cell doesn't exist.
+    pub fn add_input_callback<F: 'r + FnMut(T)>(
+        &mut self,
+        id: InputCellID,
+        callback: F,
+    ) -> Option<CallbackID> {
+        if !self.contains(CellID::Input(id)) {
+            return None;
+        }
+        let InputCellID(idx) = id;
+        let cell = &mut self.input_cells[idx];
+        let cbid = CallbackID(cell.next_cbid);
+        cell.next_cbid += 1;
+        cell.callbacks.insert(cbid, Box::new(callback));
+        Some(cbid)
+    }
+
+    // Adds a callback that is handed the reactor along with the cell's new value, so it can read
+    // other cells while it fires. A cell read this way is brought up to date first, as `value`
+    // does, so a callback reading a sibling sees the sibling's final value even if propagation
+    // hasn't reached it yet. The reactor can only be read: changing it from a callback isn't
+    // possible.
+    //
+    // Returns the ID of the just-added callback, or None if the cell doesn't exist.
+    pub fn add_callback_with_reactor<F: 'r + FnMut(T, &Reactor<'r, T>)>(
+        &mut self,
+        id: ComputeCellID,
+        mut callback: F,
+    ) -> Option<CallbackID> {
+        if !self.contains(CellID::Compute(id)) {
+            return None;
+        }
+        Some(self.insert_callback_entry(id, 0, move |v, reactor| {
+            callback(v.clone(), reactor);
+            true
+        }))
+    }
+
+    // Adds a callback whose side effects are async. It fires like any other callback, but the
+    // future it returns isn't run; it's kept until the caller collects it with `take_futures`.
+    //
+    // Returns the ID of the just-added callback, or None if the cell doesn't exist.
+    pub fn add_async_callback<F, Fut>(
+        &mut self,
+        id: ComputeCellID,
+        mut callback: F,
+    ) -> Option<CallbackID>
+    where
+        F: 'r + FnMut(T) -> Fut,
+        Fut: 'r + Future<Output = ()>,
+    {
+        if !self.contains(CellID::Compute(id)) {
+            return None;
+        }
+        let futures = Rc::clone(&self.futures);
+        Some(self.insert_callback(id, move |v| {
+            futures.borrow_mut().push(Box::pin(callback(v.clone())));
+            true
+        }))
+    }
+
+    // Returns the futures async callbacks have returned since the last call, in the order the
+    // callbacks fired, for the caller to await.
+    pub fn take_futures(&mut self) -> Vec<BoxedFuture<'r>> {
+        std::mem::take(&mut *self.futures.borrow_mut())
+    }
+
+    // Removes the specified callback, using an ID returned from add_callback.
+    //
+    // Returns an Err if either the cell or callback does not exist.
+    //
+    // A removed callback should no longer be called.
+    pub fn remove_callback(
+        &mut self,
+        cell: ComputeCellID,
+        callback: CallbackID,
+    ) -> Result<(), RemoveCallbackError> {
+        let ComputeCellID(idx) = cell;
+        if let Some(compute_cell) = self.compute_cells.get_mut(idx).filter(|c| !c.removed) {
+            if compute_cell.callbacks.get_mut().remove(&callback).is_some() {
+                Ok(())
+            } else {
+                Err(RemoveCallbackError::NonexistentCallback)
+            }
+        } else {
+            Err(RemoveCallbackError::NonexistentCell)
+        }
+    }
+
+    // Removes every callback on the specified compute cell, returning how many were removed.
+    // Callback IDs are still never reused, so the cell's future callbacks get fresh ones.
+    //
+    // Returns an Err if the cell does not exist.
+    pub fn clear_callbacks(&mut self, cell: ComputeCellID) -> Result<usize, RemoveCallbackError> {
+        let ComputeCellID(idx) = cell;
+        match self.compute_cells.get_mut(idx).filter(|c| !c.removed) {
+            Some(compute_cell) => {
+                let callbacks = compute_cell.callbacks.get_mut();
+                let count = callbacks.len();
+                callbacks.clear();
+                Ok(count)
+            }
+            None => Err(RemoveCallbackError::NonexistentCell),
+        }
+    }
+
+    // Removes a callback from an input cell, using an ID returned from `add_input_callback`.
+    //
+    // Returns an Err if either the cell or callback does not exist.
+    pub fn remove_input_callback(
+        &mut self,
+        cell: InputCellID,
+        callback: CallbackID,
+    ) -> Result<(), RemoveCallbackError> {
+        let InputCellID(idx) = cell;
+        let input_cell = self
+            .input_cells
+            .get_mut(idx)
+            .filter(|c| !c.removed)
+            .ok_or(RemoveCallbackError::NonexistentCell)?;
+        input_cell
+            .callbacks
+            .remove(&callback)
+            .map(|_| ())
+            .ok_or(RemoveCallbackError::NonexistentCallback)
+    }
+
+    // Starts holding back callbacks until `end_batch`. In between, anything can be done as usual,
+    // including setting and reading values; only the callbacks are deferred.
+    pub fn begin_batch(&mut self) {
+        self.batching = true;
+    }
+
+    // Ends a batch begun with `begin_batch`, firing the callbacks of each cell whose value at the
+    // end of the batch differs from its value at the start, once, with its final value. A cell
+    // that changed and then changed back doesn't fire. Input callbacks fire first, then compute
+    // cells' in order of rank.
+    //
+    // Does nothing if no batch is in progress.
+    pub fn end_batch(&mut self) {
+        if !std::mem::replace(&mut self.batching, false) {
+            return;
+        }
+        for cell in self.input_cells.iter_mut() {
+            if let Some(before) = cell.batched.take() {
+                let value = cell.value.clone();
+                if value != before && !self.callbacks_muted {
+                    cell.fire(&self.firing, &value);
+                }
+            }
+        }
+        let mut changed = (0..self.compute_cells.len())
+            .filter_map(|idx| {
+                let cell = &self.compute_cells[idx];
+                let before = cell.batched.borrow_mut().take()?;
+                Some((cell.rank, idx, before))
+            })
+            .collect::<Vec<_>>();
+        changed.sort_unstable_by_key(|(rank, idx, _)| (*rank, *idx));
+        let mut fired = Vec::new();
+        for (_, idx, before) in changed {
+            let cell = &self.compute_cells[idx];
+            if let Some(now) = cell.cached() {
+                let same = matches!(&before, Some(b) if cell.same(b, &now));
+                if !same && !self.callbacks_muted {
+                    cell.fire(self, &now);
+                    fired.push(ComputeCellID(idx));
+                }
+            }
+        }
+        self.fire_global(fired);
+    }
+
+    // Adds a callback that fires once per call that changes input values, e.g. `set_value`,
+    // `set_values` or `commit`, or once a `begin_set` propagation is done, after every per-cell
+    // callback, with each compute cell whose value
+    // changed and its new value, in order of rank. It doesn't fire when no compute cell changed.
+    //
+    // Like per-cell callbacks, it's held back during a batch, firing once when the batch ends,
+    // and doesn't fire while callbacks are muted. When lazy, cells change as they're read rather
+    // than when inputs are set, so it never fires.
+    pub fn add_global_callback<F: 'r + FnMut(&[(ComputeCellID, T)])>(&mut self, callback: F) {
+        self.global_callbacks.push(Box::new(callback));
+    }
+
+    // Fires the global callbacks for the compute cells that just changed, if any did and
+    // callbacks aren't held back. Cells left without a value are skipped.
+    fn fire_global(&mut self, changed: Vec<ComputeCellID>) {
+        if self.callbacks_muted || self.batching || self.global_callbacks.is_empty() {
+            return;
+        }
+        let changes = changed
+            .into_iter()
+            .filter_map(|id @ ComputeCellID(idx)| Some((id, self.compute_cells[idx].cached()?)))
+            .collect::<Vec<_>>();
+        if changes.is_empty() {
+            return;
+        }
+        let was_firing = self.firing.replace(true);
+        for callback in self.global_callbacks.iter_mut() {
+            callback(&changes);
+        }
+        self.firing.set(was_firing);
+    }
+
+    // Runs `f` in a batch, as between `begin_batch` and `end_batch`, returning its result.
+    pub fn batch<R, F: FnOnce(&mut Self) -> R>(&mut self, f: F) -> R {
+        self.begin_batch();
+        let result = f(self);
+        self.end_batch();
+        result
+    }
+
+    // Chooses between eager evaluation, the default, and lazy evaluation.
+    //
+    // When eager, setting an input recomputes every compute cell downstream of it right away, and
+    // their callbacks fire before `set_value` returns. When lazy, setting an input only marks the
+    // cells downstream of it stale; each is recomputed the next time its value is read, directly
+    // or by a cell depending on it, and its callbacks fire then, if it changed. A stale cell that's
+    // never read never fires its callbacks, and methods that report the cells a change affected,
+    // such as `set_input_values`, report nothing.
+    //
+    // Switching back to eager brings every stale cell up to date, firing their callbacks.
+    pub fn set_eager(&mut self, eager: bool) {
+        self.lazy = !eager;
+        if eager {
+            self.catch_up();
+        }
+    }
+
+    // Stops callbacks from firing until `unmute_callbacks` is called. Propagation carries on as
+    // usual, so values stay consistent and each compute cell still caches its latest value.
+    //
+    // Changes made while muted are silently absorbed: unmuting doesn't fire any catch-up
+    // callbacks, and a later callback only fires if the cell changes again.
+    pub fn mute_callbacks(&mut self) {
+        self.callbacks_muted = true;
+    }
+
+    // Lets callbacks fire again after `mute_callbacks`.
+    pub fn unmute_callbacks(&mut self) {
+        self.callbacks_muted = false;
+    }
+
+    // Returns whether callbacks are being fired, e.g. by `set_value`.
+    pub fn in_propagation(&self) -> bool {
+        self.firing.get()
+    }
+
+    // Returns a probe that reports whether this reactor is firing callbacks, for use inside
+    // callbacks.
+    pub fn propagation_probe(&self) -> PropagationProbe {
+        PropagationProbe {
+            firing: Rc::clone(&self.firing),
+        }
+    }
+
+    // Appends the current value of every input cell to `log`.
+    pub fn record(&self, log: &mut SnapshotLog<T>) {
+        log.states
+            .push(self.input_cells.iter().map(|i| i.value.clone()).collect());
+    }
+
+    // Re-applies each state in `log`, in order. Each state is set in one step, as
+    // `set_input_values` does, so callbacks fire once per state, for cells whose value it changed.
+    //
+    // Returns false, without applying anything, if any state doesn't have exactly one value per
+    // input cell.
+    pub fn replay_log(&mut self, log: &SnapshotLog<T>) -> bool {
+        if log
+            .states
+            .iter()
+            .any(|state| state.len() != self.input_cells.len())
+        {
+            return false;
+        }
+        for state in log.states.iter() {
+            let _ = self.set_input_values(state);
+        }
+        true
+    }
+
+    // Returns how many input cells there are, not counting removed ones.
+    pub fn num_inputs(&self) -> usize {
+        self.input_cells.iter().filter(|c| !c.removed).count()
+    }
+
+    // Returns how many compute cells there are, not counting removed ones.
+    pub fn num_computes(&self) -> usize {
+        self.compute_cells.iter().filter(|c| !c.removed).count()
+    }
+
+    // Returns how many cells there are, input and compute, not counting removed ones.
+    pub fn len(&self) -> usize {
+        self.num_inputs() + self.num_computes()
+    }
+
+    // Returns whether there are no cells, not counting removed ones.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // Returns the IDs of the input cells, in creation order, skipping removed cells.
+    pub fn input_ids(&self) -> impl Iterator<Item = InputCellID> {
+        self.input_cells
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| !c.removed)
+            .map(|(idx, _)| InputCellID(idx))
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+
+    // Returns the IDs of the compute cells, in creation order, skipping removed cells.
+    pub fn compute_ids(&self) -> impl Iterator<Item = ComputeCellID> {
+        // collected up front, since an iterator borrowing the cells would have to name 'r
+        self.compute_cells
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| !c.removed)
+            .map(|(idx, _)| ComputeCellID(idx))
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+
+    // Returns the IDs of every cell: the input cells, then the compute cells, each in creation
+    // order, skipping removed cells.
+    pub fn cell_ids(&self) -> impl Iterator<Item = CellID> {
+        self.input_ids()
+            .map(CellID::Input)
+            .chain(self.compute_ids().map(CellID::Compute))
+    }
+
+    // Builds a petgraph graph with a node for each cell, weighted with its ID and added in
+    // `cell_ids` order, and an edge from each dependency to each compute cell that reads it.
+    // Removed cells are left out.
+    #[cfg(feature = "petgraph")]
+    pub fn to_petgraph(&self) -> petgraph::graph::DiGraph<CellID, ()> {
+        let mut graph = petgraph::graph::DiGraph::new();
+        let nodes = self
+            .cell_ids()
+            .map(|id| (id, graph.add_node(id)))
+            .collect::<HashMap<_, _>>();
+        for (dep, client) in self.edges() {
+            graph.add_edge(nodes[&dep], nodes[&CellID::Compute(client)], ());
+        }
+        graph
+    }
+
+    // Returns the cells a compute cell depends on, in the order its function receives their
+    // values, or None if the cell doesn't exist.
+    pub fn dependencies(&self, id: ComputeCellID) -> Option<Vec<CellID>> {
+        Some(self.compute_cell(id)?.deps.clone())
+    }
+
+    // Returns the compute cells that depend directly on a cell, in ID order, or None if the cell
+    // doesn't exist. These are the cells recomputed first when the cell changes.
+    pub fn dependents(&self, id: CellID) -> Option<Vec<ComputeCellID>> {
+        if !self.contains(id) {
+            return None;
+        }
+        let mut clients = self.clients(id).iter().copied().collect::<Vec<_>>();
+        clients.sort();
+        Some(clients)
+    }
+
+    // Evaluates each dependency of a compute cell and pairs it with its ID, in the order the
+    // dependencies were declared. Dependencies shared between them are only evaluated once.
+    //
+    // Returns None if the cell doesn't exist.
+    pub fn dependency_values(&self, id: ComputeCellID) -> Option<Vec<(CellID, T)>> {
+        let cell = self.compute_cell(id)?;
+        let mut scratch = Scratch::new();
+        cell.deps
+            .iter()
+            .map(|d| self.eval_scratch(*d, &mut scratch).map(|v| (*d, v)))
+            .collect()
+    }
+
+    // Sets an input cell back to the value it was created with, propagating as `set_value` does.
+    //
+    // Returns false if the cell does not exist.
+    pub fn reset_input(&mut self, id: InputCellID) -> bool {
+        let InputCellID(idx) = id;
+        match self.input_cell(InputCellID(idx)) {
+            Some(cell) => {
+                let initial = cell.initial.clone();
+                self.set_value(id, initial)
+            }
+            None => false,
+        }
+    }
+
+    // Sets every input cell back to the value it was created with, then propagates once, as
+    // `set_values` does, so each compute cell that changes fires its callbacks once.
+    pub fn reset(&mut self) {
+        let updates = self
+            .input_cells
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| !c.removed)
+            .map(|(idx, c)| (InputCellID(idx), c.initial.clone()))
+            .collect::<Vec<_>>();
+        self.apply_inputs(updates);
+    }
+
+    // Sets the value of the specified input cell, but leaves propagating the change to the
+    // returned `Propagation`, one layer at a time.
+    //
+    // Returns None if the cell does not exist.
+    pub fn begin_set(&mut self, id: InputCellID, value: T) -> Option<Propagation<'_, 'r, T>> {
+        if !self.contains(CellID::Input(id)) {
+            return None;
+        }
+        self.catch_up();
+        let versions = self.versions();
+        let seeds = self.write_inputs(Some((id, value)));
+        let marked = self.mark_dirty(&seeds);
+        let pending = seeds
+            .into_iter()
+            .map(|cid @ ComputeCellID(cidx)| (self.compute_cells[cidx].rank, cid))
+            .collect();
+        Some(Propagation {
+            reactor: self,
+            pending,
+            marked,
+            versions,
+            changed: Vec::new(),
+        })
+    }
+
+    // Returns the value of every input cell, indexed by input cell ID. A removed input cell keeps
+    // its slot, holding its last value.
+    pub fn input_values(&self) -> Vec<T> {
+        self.input_cells.iter().map(|c| c.value.clone()).collect()
+    }
+
+    // Captures the value of every input cell, to be written back later with `restore`, e.g. for
+    // undo. A removed input cell keeps its slot, as in `input_values`.
+    pub fn checkpoint(&self) -> Checkpoint<T> {
+        Checkpoint {
+            values: self.input_values(),
+        }
+    }
+
+    // Writes the input values captured by `checkpoint` back, as `set_input_values` does: every
+    // input whose value differs is set, then the change propagates once, firing callbacks for
+    // each cell whose value changed. Returns the compute cells whose value changed.
+    //
+    // If the reactor has gained input cells since the checkpoint was taken, or the checkpoint
+    // came from another reactor with a different number of them, returns an Err and sets
+    // nothing.
+    pub fn restore(&mut self, c: &Checkpoint<T>) -> Result<Vec<ComputeCellID>, LengthMismatch> {
+        self.set_input_values(&c.values)
+    }
+
+    // Sets every input cell at once, `values` being indexed by input cell ID, then propagates
+    // once, as `commit` does. Returns the compute cells whose value changed.
+    //
+    // If there isn't exactly one value per input cell, returns an Err and sets nothing. Values in
+    // the slots of removed input cells are ignored.
+    pub fn set_input_values(&mut self, values: &[T]) -> Result<Vec<ComputeCellID>, LengthMismatch> {
+        if values.len() != self.input_cells.len() {
+            return Err(LengthMismatch {
+                expected: self.input_cells.len(),
+                actual: values.len(),
+            });
+        }
+        let updates = values
+            .iter()
+            .enumerate()
+            .filter(|(idx, _)| !self.input_cells[*idx].removed)
+            .map(|(idx, v)| (InputCellID(idx), v.clone()))
+            .collect::<Vec<_>>();
+        Ok(self.apply_inputs(updates))
+    }
+
+    // Stages a new value for an input cell without applying it. Staging the same cell twice
+    // replaces the earlier staged value.
+    //
+    // Returns false if the cell does not exist.
+    pub fn stage_value(&mut self, id: InputCellID, value: T) -> bool {
+        if !self.contains(CellID::Input(id)) {
+            return false;
+        }
+        self.staged.insert(id, value);
+        true
+    }
+
+    // Returns the value staged for an input cell, or None if nothing is staged for it.
+    pub fn staged_value(&self, id: InputCellID) -> Option<T> {
+        self.staged.get(&id).cloned()
+    }
+
+    // Applies every staged value, then propagates once, so each affected compute cell is
+    // recomputed (and its callbacks fired) at most once. Returns the compute cells whose value
+    // changed.
+    pub fn commit(&mut self) -> Vec<ComputeCellID> {
+        let staged = std::mem::take(&mut self.staged);
+        self.apply_inputs(staged)
+    }
+
+    // Discards every staged value.
+    pub fn rollback(&mut self) {
+        self.staged.clear();
+    }
+
+    // Writes each update to its input cell, then propagates once from every input whose value
+    // actually changed. The input IDs must exist.
+    fn apply_inputs(
+        &mut self,
+        updates: impl IntoIterator<Item = (InputCellID, T)>,
+    ) -> Vec<ComputeCellID> {
+        if !self.lazy {
+            // cells a dropped `Propagation` didn't reach must be current before propagating from
+            // them, since they aren't marked again
+            self.catch_up();
+        }
+        let seeds = self.write_inputs(updates);
+        let marked = self.mark_dirty(&seeds);
+        if self.lazy {
+            return Vec::new();
+        }
+        let changed = self.propagate(seeds);
+        self.settle(marked);
+        self.fire_global(changed.clone());
+        changed
+    }
+
+    // Brings every stale compute cell up to date, firing callbacks for those that changed. Cells
+    // go in order of rank, so each one's dependencies are already current when it's recomputed.
+    fn catch_up(&self) {
+        let mut stale = (0..self.compute_cells.len())
+            .filter(|&idx| self.compute_cells[idx].dirty.get() && !self.compute_cells[idx].removed)
+            .map(|idx| (self.compute_cells[idx].rank, idx))
+            .collect::<Vec<_>>();
+        stale.sort_unstable();
+        for (_, idx) in stale {
+            self.compute_cells[idx].call(self);
+        }
+    }
+
+    // Brings a stale compute cell's stale ancestors up to date in order of rank, as `catch_up`
+    // does for every cell, so reading a long stale chain doesn't recurse down it.
+    fn catch_up_to(&self, id: ComputeCellID) {
+        let mut seen = HashSet::new();
+        let mut stale = Vec::new();
+        let mut stack = vec![id];
+        while let Some(ComputeCellID(idx)) = stack.pop() {
+            let cell = &self.compute_cells[idx];
+            if !cell.dirty.get() || cell.removed || !seen.insert(idx) {
+                continue;
+            }
+            stale.push((cell.rank, idx));
+            stack.extend(cell.deps.iter().filter_map(|d| match d {
+                CellID::Compute(dep) => Some(*dep),
+                CellID::Input(_) => None,
+            }));
+        }
+        stale.sort_unstable();
+        for (_, idx) in stale {
+            self.compute_cells[idx].call(self);
+        }
+    }
+
+    // Writes each update to its input cell, returning the clients of every input whose value
+    // actually changed. The input IDs must exist.
+    fn write_inputs(
+        &mut self,
+        updates: impl IntoIterator<Item = (InputCellID, T)>,
+    ) -> BTreeSet<ComputeCellID> {
+        self.sync_dependencies();
+        // an input written more than once counts once, with its final value, and not at all if
+        // that's the value it started with
+        let mut before = BTreeMap::new();
+        for (InputCellID(idx), value) in updates {
+            let cell = &mut self.input_cells[idx];
+            if cell.value != value {
+                let old = std::mem::replace(&mut cell.value, value);
+                before.entry(idx).or_insert(old);
+            }
+        }
+        let mut seeds = BTreeSet::new();
+        let mut bumped = false;
+        for (idx, old) in before {
+            let cell = &mut self.input_cells[idx];
+            if cell.value == old {
+                continue;
+            }
+            if !bumped {
+                self.generation += 1;
+                bumped = true;
+            }
+            let value = cell.value.clone();
+            cell.history.record(self.generation, value.clone());
+            cell.version += 1;
+            if self.batching {
+                if cell.batched.is_none() {
+                    cell.batched = Some(old);
+                }
+            } else if !self.callbacks_muted {
+                cell.fire(&self.firing, &value);
+            }
+            seeds.extend(cell.clients.iter().copied());
+        }
+        seeds
+    }
+
+    // Recomputes a compute cell whose definition was just edited, then propagates from it, as
+    // `apply_inputs` does from changed inputs, if its value changed.
+    fn recompute_edited(&mut self, id: ComputeCellID) {
+        if !self.lazy {
+            self.catch_up();
+        }
+        self.recompute_and_propagate(id);
+        self.sync_dependencies();
+    }
+
+    // Recomputes a compute cell even if it's current, then, if its value changed, propagates the
+    // change as `apply_inputs` does from changed inputs: right away when eager, by marking its
+    // clients stale when lazy. Returns the cell's new value.
+    fn recompute_and_propagate(&self, id: ComputeCellID) -> Option<T> {
+        let ComputeCellID(idx) = id;
+        let cell = &self.compute_cells[idx];
+        let version = cell.version.get();
+        let value = cell.recompute(self);
+        if cell.version.get() != version {
+            let seeds = cell.clients.iter().copied().collect::<BTreeSet<_>>();
+            let marked = self.mark_dirty(&seeds);
+            if !self.lazy {
+                self.propagate(seeds);
+                // cells propagation pruned away are dirty, but none of their dependencies changed
+                for ComputeCellID(idx) in marked {
+                    self.compute_cells[idx].dirty.set(false);
+                }
+            }
+        }
+        value
+    }
+
+    // Tidies up after propagating from a change that flagged `marked` dirty.
+    fn settle(&mut self, marked: Vec<ComputeCellID>) {
+        // cells propagation pruned away are dirty, but none of their dependencies changed
+        for ComputeCellID(idx) in marked {
+            self.compute_cells[idx].dirty.set(false);
+        }
+        self.sync_dependencies();
+    }
+
+    // Flags `seeds` and everything downstream of them as needing recomputation, returning the
+    // cells flagged.
+    fn mark_dirty(&self, seeds: &BTreeSet<ComputeCellID>) -> Vec<ComputeCellID> {
+        let mut queue = seeds.iter().copied().collect::<Vec<_>>();
+        let mut marked = Vec::new();
+        while let Some(id @ ComputeCellID(idx)) = queue.pop() {
+            let cell = &self.compute_cells[idx];
+            if !cell.dirty.replace(true) {
+                queue.extend(cell.clients.iter().copied());
+                marked.push(id);
+            }
+        }
+        marked
+    }
+
+    // Moves each dynamic cell's edges over to the cells it read when it was last evaluated.
+    fn sync_dependencies(&mut self) {
+        let mut rewired = false;
+        for idx in 0..self.compute_cells.len() {
+            let cid = ComputeCellID(idx);
+            let deps = match self.compute_cells[idx].pending_deps.get_mut().take() {
+                Some(deps) if deps != self.compute_cells[idx].deps => deps,
+                _ => continue,
+            };
+            for d in std::mem::replace(&mut self.compute_cells[idx].deps, deps.clone()) {
+                self.clients_mut(d).remove(&cid);
+            }
+            for d in deps {
+                self.clients_mut(d).insert(cid);
+            }
+            rewired = true;
+        }
+        if rewired {
+            self.rerank();
+        }
+    }
+
+    // Recomputes every compute cell's rank, visiting dependencies before their clients.
+    fn rerank(&mut self) {
+        for ComputeCellID(idx) in self.topological_order() {
+            self.compute_cells[idx].rank = self.rank_above(&self.compute_cells[idx].deps);
+        }
+    }
+
+    // Returns the member of `deps` through which `target` can be reached by following
+    // dependencies, if any. Creating a cell with these dependencies under the ID `target` would
+    // close a cycle through that member.
+    fn path_to(&self, deps: &[CellID], target: CellID) -> Option<CellID> {
+        deps.iter().copied().find(|dep| {
+            let mut seen = HashSet::new();
+            let mut stack = vec![*dep];
+            while let Some(id) = stack.pop() {
+                if id == target {
+                    return true;
+                }
+                if let CellID::Compute(ComputeCellID(idx)) = id {
+                    if seen.insert(idx) {
+                        stack.extend(self.compute_cells[idx].deps.iter().copied());
+                    }
+                }
+            }
+            false
+        })
+    }
+
+    // Returns the rank of a compute cell with the given dependencies.
+    fn rank_above(&self, deps: &[CellID]) -> usize {
+        1 + deps
+            .iter()
+            .map(|d| match d {
+                CellID::Input(_) => 0,
+                CellID::Compute(ComputeCellID(idx)) => self.compute_cells[*idx].rank,
+            })
+            .max()
+            .unwrap_or(0)
+    }
+
+    fn clients_mut(&mut self, id: CellID) -> &mut HashSet<ComputeCellID> {
+        match id {
+            CellID::Input(InputCellID(idx)) => &mut self.input_cells[idx].clients,
+            CellID::Compute(ComputeCellID(idx)) => &mut self.compute_cells[idx].clients,
+        }
+    }
+
+    // Returns the compute cell whose value changed most recently, or None if no compute cell has
+    // changed since it was created.
+    pub fn most_recently_changed(&self) -> Option<ComputeCellID> {
+        self.compute_cells
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| !c.removed && c.changed_at.get() > 0)
+            .max_by_key(|(_, c)| c.changed_at.get())
+            .map(|(idx, _)| ComputeCellID(idx))
+    }
+
+    // Returns every compute cell ordered so that each one comes after all the compute cells it
+    // depends on, with ties broken by ID. The order is worked out from the dependency graph
+    // rather than assumed from creation order.
+    pub fn topological_order(&self) -> Vec<ComputeCellID> {
+        let mut waiting = HashMap::new();
+        let mut ready = BTreeSet::new();
+        for (idx, cell) in self.compute_cells.iter().enumerate() {
+            if cell.removed {
+                continue;
+            }
+            let n = cell
+                .deps
+                .iter()
+                .filter(|d| matches!(d, CellID::Compute(_)))
+                .count();
+            if n == 0 {
+                ready.insert(ComputeCellID(idx));
+            } else {
+                waiting.insert(ComputeCellID(idx), n);
+            }
+        }
+        let mut order = Vec::new();
+        while let Some(id @ ComputeCellID(idx)) = ready.pop_first() {
+            order.push(id);
+            for client in self.compute_cells[idx].clients.iter() {
+                if let Entry::Occupied(mut e) = waiting.entry(*client) {
+                    *e.get_mut() -= 1;
+                    if *e.get() == 0 {
+                        e.remove();
+                        ready.insert(*client);
+                    }
+                }
+            }
+        }
+        order
+    }
+
+    // Returns the compute cells that nothing observes: no other cell depends on them and they
+    // have no callbacks. Whether anyone has read a cell's value doesn't come into it, since
+    // reads aren't tracked.
+    pub fn orphans(&self) -> Vec<ComputeCellID> {
+        self.compute_cells
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| !c.removed && c.clients.is_empty() && c.callbacks.borrow().is_empty())
+            .map(|(idx, _)| ComputeCellID(idx))
+            .collect()
+    }
+
+    // Assigns each cell its longest distance from any input: inputs are rank 0 and a compute
+    // cell is one more than the highest rank among its dependencies.
+    pub fn cell_ranks(&self) -> HashMap<CellID, usize> {
+        let inputs = self
+            .input_cells
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| !c.removed)
+            .map(|(idx, _)| (CellID::Input(InputCellID(idx)), 0));
+        let computes = self
+            .compute_cells
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| !c.removed)
+            .map(|(idx, c)| (CellID::Compute(ComputeCellID(idx)), c.rank));
+        inputs.chain(computes).collect()
+    }
+
+    // Returns a single cell's rank, as `cell_ranks` assigns them: its longest distance from any
+    // input. Ranks are kept up to date as the graph changes, so this doesn't walk the graph.
+    //
+    // Returns None if the cell doesn't exist.
+    pub fn depth(&self, id: CellID) -> Option<usize> {
+        match id {
+            CellID::Input(iid) => self.input_cell(iid).map(|_| 0),
+            CellID::Compute(cid) => self.compute_cell(cid).map(|c| c.rank),
+        }
+    }
+
+    // Returns the compute cells, in ID order, whose value as of generation `to_gen` differs from
+    // their value as of `from_gen`, paired with their value as of `to_gen`. A cell that changed
+    // and then changed back in between isn't included, so applying the result to the values as
+    // of `from_gen` yields the values as of `to_gen`.
+    //
+    // Returns an empty Vec if `from_gen` is after `to_gen`, or either is outside the window
+    // `value_at_generation` can see.
+    pub fn changes_between(&self, from_gen: u64, to_gen: u64) -> Vec<(ComputeCellID, T)> {
+        if from_gen > to_gen
+            || to_gen > self.generation
+            || from_gen + GENERATION_WINDOW < self.generation
+        {
+            return Vec::new();
+        }
+        (0..self.compute_cells.len())
+            .map(ComputeCellID)
+            .filter_map(|id| {
+                let cell = CellID::Compute(id);
+                let new = self.value_at_generation(cell, to_gen)?;
+                if self.value_at_generation(cell, from_gen).as_ref() == Some(&new) {
+                    return None;
+                }
+                Some((id, new))
+            })
+            .collect()
+    }
+
+    // Adds each callback to its compute cell, returning the IDs in the same order, with None for
+    // any callback whose cell doesn't exist.
+    pub fn add_callbacks(
+        &mut self,
+        subs: Vec<(ComputeCellID, BoxedCallback<'r, T>)>,
+    ) -> Vec<Option<CallbackID>> {
+        subs.into_iter()
+            .map(|(id, callback)| self.add_callback(id, callback))
+            .collect()
+    }
+
+    // Adds a callback with the given priority. When a cell changes, its callbacks fire from
+    // highest priority to lowest, and in the order they were added among equal priorities.
+    // Callbacks added with `add_callback` have priority 0.
+    //
+    // Returns the ID of the just-added callback, or None if the cell doesn't exist.
+    pub fn add_callback_with_priority<F: 'r + FnMut(T)>(
+        &mut self,
+        id: ComputeCellID,
+        priority: i32,
+        mut callback: F,
+    ) -> Option<CallbackID> {
+        if !self.contains(CellID::Compute(id)) {
+            return None;
+        }
+        Some(self.insert_callback_with_priority(id, priority, move |v| {
+            callback(v.clone());
+            true
+        }))
+    }
+
+    // Enables or disables a callback. A disabled callback stays registered but doesn't fire, and
+    // isn't called to catch up once it's enabled again.
+    //
+    // Returns an Err if either the cell or callback does not exist.
+    pub fn set_callback_enabled(
+        &mut self,
+        cell: ComputeCellID,
         callback: CallbackID,
+        enabled: bool,
     ) -> Result<(), RemoveCallbackError> {
         let ComputeCellID(idx) = cell;
-        if let Some(compute_cell) = self.compute_cells.get_mut(idx) {
-            if compute_cell.callbacks.remove(&callback).is_some() {
-                return Ok(());
+        let compute_cell = self
+            .compute_cells
+            .get_mut(idx)
+            .filter(|c| !c.removed)
+            .ok_or(RemoveCallbackError::NonexistentCell)?;
+        let entry = compute_cell
+            .callbacks
+            .get_mut()
+            .get_mut(&callback)
+            .ok_or(RemoveCallbackError::NonexistentCallback)?;
+        entry.enabled = enabled;
+        Ok(())
+    }
+
+    // Returns how many callbacks are registered on a compute cell, including disabled ones, or
+    // None if the cell doesn't exist. A callback whose `Subscription` was dropped is still
+    // counted until the cell next changes, when it's removed.
+    pub fn callback_count(&self, id: ComputeCellID) -> Option<usize> {
+        Some(self.compute_cell(id)?.callbacks.borrow().len())
+    }
+
+    // Returns whether `cb` is registered on a compute cell. False if either doesn't exist.
+    pub fn has_callback(&self, id: ComputeCellID, cb: CallbackID) -> bool {
+        self.compute_cell(id)
+            .is_some_and(|c| c.callbacks.borrow().contains_key(&cb))
+    }
+
+    // Describes each callback on a compute cell, in the order they fire.
+    //
+    // Returns None if the cell doesn't exist.
+    pub fn callbacks_info(&self, id: ComputeCellID) -> Option<Vec<CallbackInfo>> {
+        let ComputeCellID(idx) = id;
+        let cell = self.compute_cell(ComputeCellID(idx))?;
+        let callbacks = cell.callbacks.borrow();
+        Some(
+            cell.callback_order()
+                .into_iter()
+                .map(|cbid| CallbackInfo {
+                    id: cbid,
+                    priority: callbacks[&cbid].priority,
+                    enabled: callbacks[&cbid].enabled,
+                })
+                .collect(),
+        )
+    }
+
+    // Adds a callback that stays registered only while the returned `Subscription` is kept.
+    //
+    // Returns None if the cell doesn't exist.
+    pub fn subscribe<F: 'r + FnMut(T)>(
+        &mut self,
+        id: ComputeCellID,
+        mut callback: F,
+    ) -> Option<Subscription> {
+        if !self.contains(CellID::Compute(id)) {
+            return None;
+        }
+        let live = Rc::new(Cell::new(true));
+        let alive = Rc::clone(&live);
+        let cbid = self.insert_callback(id, move |v| {
+            if alive.get() {
+                callback(v.clone());
+            }
+            alive.get()
+        });
+        Some(Subscription {
+            cell: id,
+            id: cbid,
+            live,
+        })
+    }
+
+    // Adds a callback that fires at most `n` times, and is then removed from the cell.
+    //
+    // Returns the ID of the just-added callback, or None if the cell doesn't exist.
+    pub fn add_callback_take<F: 'r + FnMut(T)>(
+        &mut self,
+        id: ComputeCellID,
+        n: usize,
+        mut callback: F,
+    ) -> Option<CallbackID> {
+        if !self.contains(CellID::Compute(id)) {
+            return None;
+        }
+        if n == 0 {
+            // a callback allowed no firings is spent already
+            return Some(self.reserve_callback_id(id));
+        }
+
+        let mut remaining = n;
+        Some(self.insert_callback(id, move |v| {
+            callback(v.clone());
+            remaining -= 1;
+            remaining > 0
+        }))
+    }
+
+    // Adds a callback that fires on the cell's next change only, and is then removed from the
+    // cell, as `add_callback_take` does with `n` of 1.
+    //
+    // Returns the ID of the just-added callback, or None if the cell doesn't exist.
+    pub fn add_once_callback<F: 'r + FnMut(T)>(
+        &mut self,
+        id: ComputeCellID,
+        callback: F,
+    ) -> Option<CallbackID> {
+        self.add_callback_take(id, 1, callback)
+    }
+
+    // Adds a callback that decides, each time it fires, whether to stay registered: one that
+    // returns `CallbackControl::Remove` is removed from the cell once every callback has fired
+    // for the current change.
+    //
+    // Returns the ID of the just-added callback, or None if the cell doesn't exist.
+    pub fn add_controlled_callback<F: 'r + FnMut(T) -> CallbackControl>(
+        &mut self,
+        id: ComputeCellID,
+        mut callback: F,
+    ) -> Option<CallbackID> {
+        if !self.contains(CellID::Compute(id)) {
+            return None;
+        }
+        Some(self.insert_callback(id, move |v| callback(v.clone()) == CallbackControl::Keep))
+    }
+
+    // Adds a callback that fires on the first change of the cell's value since the cell was
+    // created, and is then removed. If the cell has already changed since its creation, the
+    // callback will never fire.
+    //
+    // Returns the ID of the just-added callback, or None if the cell doesn't exist.
+    pub fn add_first_change_callback<F: 'r + FnOnce(T)>(
+        &mut self,
+        id: ComputeCellID,
+        callback: F,
+    ) -> Option<CallbackID> {
+        let ComputeCellID(idx) = id;
+        let cell = self.compute_cell(ComputeCellID(idx))?;
+        if cell.changed_at.get() > 0 {
+            return Some(self.reserve_callback_id(id));
+        }
+
+        let mut callback = Some(callback);
+        Some(self.insert_callback(id, move |v| {
+            if let Some(f) = callback.take() {
+                f(v.clone());
+            }
+            false
+        }))
+    }
+
+    // Hands out a callback ID on an existing compute cell without registering anything, for
+    // callbacks that could never fire.
+    fn reserve_callback_id(&mut self, id: ComputeCellID) -> CallbackID {
+        let ComputeCellID(idx) = id;
+        let cbid = CallbackID(self.compute_cells[idx].next_cbid);
+        self.compute_cells[idx].next_cbid += 1;
+        cbid
+    }
+
+    // Registers a callback on an existing compute cell, returning its new ID.
+    fn insert_callback<F: 'r + FnMut(&T) -> bool>(
+        &mut self,
+        id: ComputeCellID,
+        callback: F,
+    ) -> CallbackID {
+        self.insert_callback_with_priority(id, 0, callback)
+    }
+
+    fn insert_callback_with_priority<F: 'r + FnMut(&T) -> bool>(
+        &mut self,
+        id: ComputeCellID,
+        priority: i32,
+        mut callback: F,
+    ) -> CallbackID {
+        self.insert_callback_entry(id, priority, move |v, _| callback(v))
+    }
+
+    // Registers a callback on an existing compute cell, handing it the reactor as well as the
+    // value, and returns its new ID.
+    fn insert_callback_entry<F: 'r + FnMut(&T, &Reactor<'r, T>) -> bool>(
+        &mut self,
+        id: ComputeCellID,
+        priority: i32,
+        callback: F,
+    ) -> CallbackID {
+        let ComputeCellID(idx) = id;
+        let cell = &mut self.compute_cells[idx];
+        let cbid = CallbackID(cell.next_cbid);
+        cell.next_cbid += 1;
+        let entry = CallbackEntry {
+            fun: RefCell::new(Box::new(callback)),
+            priority,
+            enabled: true,
+        };
+        cell.callbacks.get_mut().insert(cbid, entry);
+        cbid
+    }
+
+    // Compares this reactor's graph to `other`'s. Edges present only in `other` are reported as
+    // added and edges present only here as removed, both sorted; the deltas are `other`'s cell
+    // counts minus ours, not counting removed cells.
+    pub fn topology_diff(&self, other: &Reactor<'r, T>) -> TopologyDiff {
+        let ours = self.edges();
+        let theirs = other.edges();
+        TopologyDiff {
+            added_edges: theirs.difference(&ours).copied().collect(),
+            removed_edges: ours.difference(&theirs).copied().collect(),
+            input_delta: other.num_inputs() as isize - self.num_inputs() as isize,
+            compute_delta: other.num_computes() as isize - self.num_computes() as isize,
+        }
+    }
+
+    // Renders the graph in Graphviz DOT format: input cells as boxes, compute cells as ellipses,
+    // and an edge from each dependency to each cell that reads it. Each node is labeled with the
+    // cell's key, if it has one, and its current value. Compute cells show their cached value,
+    // so nothing is evaluated.
+    pub fn to_dot(&self) -> String {
+        let node = |id: CellID| match id {
+            CellID::Input(InputCellID(idx)) => format!("i{}", idx),
+            CellID::Compute(ComputeCellID(idx)) => format!("c{}", idx),
+        };
+        let label = |id: CellID| {
+            let name = match (self.cell_keys.get(&id), id) {
+                (Some(key), _) => key.clone(),
+                (None, CellID::Input(InputCellID(idx))) => format!("input {}", idx),
+                (None, CellID::Compute(ComputeCellID(idx))) => format!("compute {}", idx),
+            };
+            let value = match self.cached_value(id) {
+                Some(v) => format!("{:?}", v),
+                None => "?".to_string(),
+            };
+            format!("{} = {}", name, value)
+                .replace('\\', "\\\\")
+                .replace('"', "\\\"")
+        };
+
+        let mut dot = String::from("digraph reactor {\n");
+        for idx in 0..self.input_cells.len() {
+            let id = CellID::Input(InputCellID(idx));
+            if self.contains(id) {
+                dot += &format!("    {} [shape=box, label=\"{}\"];\n", node(id), label(id));
+            }
+        }
+        for idx in 0..self.compute_cells.len() {
+            let id = CellID::Compute(ComputeCellID(idx));
+            if self.contains(id) {
+                dot += &format!(
+                    "    {} [shape=ellipse, label=\"{}\"];\n",
+                    node(id),
+                    label(id)
+                );
+            }
+        }
+        for (dep, client) in self.edges() {
+            dot += &format!("    {} -> {};\n", node(dep), node(CellID::Compute(client)));
+        }
+        dot.push('}');
+        dot.push('\n');
+        dot
+    }
+
+    // Renders every cell's current value as a JSON object, for logging, keyed by `"input:0"`,
+    // `"compute:3"` and so on, in ID order. Values are written with `Display`: as they are if
+    // that's a JSON number, `true`, `false` or `null`, and as strings otherwise. A compute cell
+    // without a value, because its function failed, maps to `null`. Each cell is evaluated at
+    // most once, and only if it's stale.
+    pub fn to_json_values(&self) -> String
+    where
+        T: Display,
+    {
+        let fields = self
+            .cell_ids()
+            .map(|id| {
+                let key = match id {
+                    CellID::Input(InputCellID(idx)) => format!("input:{}", idx),
+                    CellID::Compute(ComputeCellID(idx)) => format!("compute:{}", idx),
+                };
+                let value = match self.value(id) {
+                    Some(v) => json_scalar(&v.to_string()),
+                    None => "null".to_string(),
+                };
+                format!("{}:{}", json_string(&key), value)
+            })
+            .collect::<Vec<_>>();
+        format!("{{{}}}", fields.join(","))
+    }
+
+    // Renders a compute cell's formula as a nested expression down to its inputs, with each
+    // compute cell applied to its dependencies in order, e.g. `total(price, tax(price, rate))`.
+    // Cells are named by their key, or `i0`, `c0` and so on if they have none. Dependencies more
+    // than `MAX_EXPLAIN_DEPTH` levels down are shown as `...`.
+    //
+    // Returns an empty String if the cell doesn't exist.
+    pub fn explain(&self, id: ComputeCellID) -> String {
+        if !self.contains(CellID::Compute(id)) {
+            return String::new();
+        }
+        let mut out = String::new();
+        self.explain_into(CellID::Compute(id), 0, &mut out);
+        out
+    }
+
+    fn explain_into(&self, id: CellID, depth: usize, out: &mut String) {
+        match self.cell_keys.get(&id) {
+            Some(key) => out.push_str(key),
+            None => match id {
+                CellID::Input(InputCellID(idx)) => out.push_str(&format!("i{}", idx)),
+                CellID::Compute(ComputeCellID(idx)) => out.push_str(&format!("c{}", idx)),
+            },
+        }
+        if let CellID::Compute(ComputeCellID(idx)) = id {
+            out.push('(');
+            if depth >= MAX_EXPLAIN_DEPTH {
+                out.push_str("...");
             } else {
-                return Err(RemoveCallbackError::NonexistentCallback);
+                for (i, dep) in self.compute_cells[idx].deps.iter().enumerate() {
+                    if i > 0 {
+                        out.push_str(", ");
+                    }
+                    self.explain_into(*dep, depth + 1, out);
+                }
             }
-        } else {
-            Err(RemoveCallbackError::NonexistentCell)
+            out.push(')');
+        }
+    }
+
+    // Returns every dependency edge in the graph.
+    fn edges(&self) -> BTreeSet<(CellID, ComputeCellID)> {
+        self.compute_cells
+            .iter()
+            .enumerate()
+            .flat_map(|(idx, c)| c.deps.iter().map(move |d| (*d, ComputeCellID(idx))))
+            .collect()
+    }
+
+    // Retrieves the value of the cell, treating compute cells' cached values according to
+    // `policy`. Input cells are unaffected by the policy.
+    //
+    // Returns None if the cell does not exist.
+    pub fn value_with_policy(&self, id: CellID, policy: CachePolicy) -> Option<T> {
+        match policy {
+            CachePolicy::Always => self.cached_value(id),
+            CachePolicy::Never => self.eval_scratch(id, &mut Scratch::new()),
+            CachePolicy::Session => match id {
+                CellID::Compute(cid) => {
+                    self.compute_cell(cid)?;
+                    self.recompute_and_propagate(cid)
+                }
+                CellID::Input(_) => self.value(id),
+            },
+        }
+    }
+
+    // Dry-runs every compute cell in a scratch evaluation and reports each one that can't be
+    // evaluated, rather than stopping at the first: cells whose function panics, cells with a
+    // missing dependency, and cells depending on a cell that failed. Failures are reported in ID
+    // order.
+    //
+    // Cached values and callbacks are left alone. Panics are caught, but the panic hook still
+    // runs, so their messages are printed as usual.
+    pub fn validate_all(&self) -> Vec<(ComputeCellID, ComputeError)> {
+        let mut values = HashMap::new();
+        let mut failures = Vec::new();
+        for cid @ ComputeCellID(idx) in self.topological_order() {
+            let cell = &self.compute_cells[idx];
+            let bad_dep = cell.deps.iter().find_map(|d| match *d {
+                CellID::Input(InputCellID(i)) if i >= self.input_cells.len() => {
+                    Some(ComputeError::MissingDependency(*d))
+                }
+                CellID::Compute(dep @ ComputeCellID(i)) if !values.contains_key(&dep) => {
+                    if i >= self.compute_cells.len() {
+                        Some(ComputeError::MissingDependency(*d))
+                    } else {
+                        Some(ComputeError::FailedDependency(dep))
+                    }
+                }
+                _ => None,
+            });
+            if let Some(err) = bad_dep {
+                failures.push((cid, err));
+                continue;
+            }
+
+            let read = |c| match c {
+                CellID::Input(InputCellID(i)) => {
+                    self.input_cell(InputCellID(i)).map(|i| i.value.clone())
+                }
+                CellID::Compute(id) => values.get(&id).cloned(),
+            };
+            match panic::catch_unwind(AssertUnwindSafe(|| cell.evaluate(&read, false))) {
+                Ok(Some(v)) => {
+                    values.insert(cid, v);
+                }
+                Ok(None) => {
+                    let msg = cell.fun.error(&cell.deps, &read).unwrap_or_default();
+                    failures.push((cid, ComputeError::Failed(msg)));
+                }
+                Err(payload) => {
+                    let msg = if let Some(m) = payload.downcast_ref::<&str>() {
+                        m.to_string()
+                    } else if let Some(m) = payload.downcast_ref::<String>() {
+                        m.clone()
+                    } else {
+                        String::from("<non-string panic payload>")
+                    };
+                    failures.push((cid, ComputeError::Panicked(msg)));
+                }
+            }
+        }
+        failures.sort_by_key(|(id, _)| *id);
+        failures
+    }
+
+    // Evaluates a cell as if `overridden` computed its value with `f` instead of its own
+    // function. This is a scratch evaluation: the reactor, including `overridden`'s function and
+    // every cached value, is left as it was, and no callbacks fire.
+    //
+    // Returns None if either cell does not exist.
+    pub fn value_with_fn_override<F: Fn(&[T]) -> T>(
+        &self,
+        id: CellID,
+        overridden: ComputeCellID,
+        f: F,
+    ) -> Option<T> {
+        let ComputeCellID(idx) = overridden;
+        self.compute_cell(ComputeCellID(idx))?;
+        let mut scratch = Scratch::new();
+        scratch.formula = Some((overridden, &f));
+        self.eval_scratch(id, &mut scratch)
+    }
+
+    // Returns the cells whose value would differ between setting the input to `a` and setting it
+    // to `b`, comparing two scratch evaluations. The reactor itself is left as it was.
+    //
+    // Returns an empty Vec if the input does not exist.
+    pub fn divergent_cells(&self, id: InputCellID, a: T, b: T) -> Vec<CellID> {
+        if !self.contains(CellID::Input(id)) {
+            return Vec::new();
+        }
+        let mut divergent = Vec::new();
+        if a != b {
+            divergent.push(CellID::Input(id));
+        }
+        let mut with_a = Scratch::new();
+        with_a.inputs.insert(id, a);
+        let mut with_b = Scratch::new();
+        with_b.inputs.insert(id, b);
+
+        for cidx in 0..self.compute_cells.len() {
+            let cell = CellID::Compute(ComputeCellID(cidx));
+            if self.eval_scratch(cell, &mut with_a) != self.eval_scratch(cell, &mut with_b) {
+                divergent.push(cell);
+            }
+        }
+        divergent
+    }
+
+    // Evaluates just `outputs` and the cells they transitively depend on, in a single scratch
+    // pass shared between them, so a cell feeding several outputs is evaluated once. Cells outside
+    // that set are never evaluated. Cached values are left alone and no callbacks fire.
+    //
+    // Outputs that don't exist are left out of the result.
+    pub fn evaluate_subset(&self, outputs: &[ComputeCellID]) -> HashMap<ComputeCellID, T> {
+        let mut scratch = Scratch::new();
+        outputs
+            .iter()
+            .filter_map(|id| Some((*id, self.eval_scratch(CellID::Compute(*id), &mut scratch)?)))
+            .collect()
+    }
+
+    // Returns the current value of every cell that exists, keyed by ID. Values are read as `value`
+    // reads them, so each compute cell is evaluated at most once, and only if it's stale.
+    pub fn snapshot(&self) -> HashMap<CellID, T> {
+        self.cell_ids()
+            .filter_map(|id| self.value(id).map(|v| (id, v)))
+            .collect()
+    }
+
+    // Evaluates every cell in a single scratch pass and captures the results. Each compute cell
+    // is evaluated exactly once, however many cells depend on it. Cached values are left alone
+    // and no callbacks fire. A removed cell keeps its slot, holding its last value, or None in a
+    // reactor rebuilt by `from_snapshot`, which never had one; a cell whose function is failing
+    // holds None too.
+    pub fn full_snapshot(&self) -> FullSnapshot<T> {
+        let mut scratch = Scratch::new();
+        FullSnapshot {
+            inputs: self.input_cells.iter().map(|i| i.value.clone()).collect(),
+            computes: (0..self.compute_cells.len())
+                .map(|idx| {
+                    let cell = &self.compute_cells[idx];
+                    if cell.removed {
+                        return cell.cached();
+                    }
+                    self.eval_scratch(CellID::Compute(ComputeCellID(idx)), &mut scratch)
+                        .or_else(|| cell.cached())
+                })
+                .collect(),
+        }
+    }
+
+    // Returns whether `a` depends on `b`, directly or transitively. Every cell counts as
+    // depending on itself, so an input cell depends only on itself.
+    //
+    // Returns false if either cell doesn't exist.
+    pub fn depends_on(&self, a: CellID, b: CellID) -> bool {
+        if !self.contains(a) || !self.contains(b) {
+            return false;
+        }
+        match a {
+            _ if a == b => true,
+            CellID::Input(_) => false,
+            CellID::Compute(ComputeCellID(idx)) => {
+                self.path_to(&self.compute_cells[idx].deps, b).is_some()
+            }
+        }
+    }
+
+    // Lists every dependency path from `from` to `to`, each starting with `from` and ending with
+    // `to`. Paths are explored in ID order, and at most `MAX_PATHS` of them are returned, so a
+    // dense graph can't blow up the result.
+    //
+    // Returns an empty Vec if either cell doesn't exist or `to` doesn't depend on `from`.
+    pub fn all_paths(&self, from: CellID, to: CellID) -> Vec<Vec<CellID>> {
+        let mut paths = Vec::new();
+        if self.contains(from) && self.contains(to) {
+            self.extend_paths(&mut vec![from], to, &mut paths);
+        }
+        paths
+    }
+
+    fn extend_paths(&self, path: &mut Vec<CellID>, to: CellID, paths: &mut Vec<Vec<CellID>>) {
+        let last = *path.last().unwrap();
+        if last == to {
+            paths.push(path.clone());
+            return;
+        }
+        let mut clients = self.clients(last).iter().copied().collect::<Vec<_>>();
+        clients.sort();
+        for c in clients {
+            if paths.len() >= MAX_PATHS {
+                return;
+            }
+            path.push(CellID::Compute(c));
+            self.extend_paths(path, to, paths);
+            path.pop();
+        }
+    }
+
+    fn contains(&self, id: CellID) -> bool {
+        match id {
+            CellID::Input(iid) => self.input_cell(iid).is_some(),
+            CellID::Compute(cid) => self.compute_cell(cid).is_some(),
+        }
+    }
+
+    // Returns the input cell with the given ID, or None if it doesn't exist or was removed.
+    fn input_cell(&self, id: InputCellID) -> Option<&InputCell<'r, T>> {
+        let InputCellID(idx) = id;
+        self.input_cells.get(idx).filter(|c| !c.removed)
+    }
+
+    // Returns the compute cell with the given ID, or None if it doesn't exist or was removed.
+    fn compute_cell(&self, id: ComputeCellID) -> Option<&ComputeCell<'r, T>> {
+        let ComputeCellID(idx) = id;
+        self.compute_cells.get(idx).filter(|c| !c.removed)
+    }
+
+    fn clients(&self, id: CellID) -> &HashSet<ComputeCellID> {
+        match id {
+            CellID::Input(InputCellID(idx)) => &self.input_cells[idx].clients,
+            CellID::Compute(ComputeCellID(idx)) => &self.compute_cells[idx].clients,
+        }
+    }
+
+    // Finds the boundary of a set of cells: the cells in the set that depend on a cell outside
+    // it (the partition's inputs), and the cells in the set that a cell outside it depends on
+    // (the partition's outputs). Both are sorted; cells that don't exist are ignored.
+    pub fn subgraph_boundary(&self, cells: &HashSet<CellID>) -> (Vec<CellID>, Vec<CellID>) {
+        let mut members = cells
+            .iter()
+            .copied()
+            .filter(|c| self.contains(*c))
+            .collect::<Vec<_>>();
+        members.sort();
+        let inputs = members
+            .iter()
+            .copied()
+            .filter(|c| match c {
+                CellID::Input(_) => false,
+                CellID::Compute(ComputeCellID(idx)) => self.compute_cells[*idx]
+                    .deps
+                    .iter()
+                    .any(|d| !cells.contains(d)),
+            })
+            .collect();
+        let outputs = members
+            .iter()
+            .copied()
+            .filter(|c| {
+                self.clients(*c)
+                    .iter()
+                    .any(|client| !cells.contains(&CellID::Compute(*client)))
+            })
+            .collect();
+        (inputs, outputs)
+    }
+
+    // Groups the cells into weakly connected components: two cells are in the same component if
+    // a chain of dependency edges, followed in either direction, joins them. Each component is
+    // sorted, and the components are ordered by their first cell.
+    pub fn components(&self) -> Vec<Vec<CellID>> {
+        let mut seen = HashSet::new();
+        let mut components = Vec::new();
+        for start in self.cell_ids() {
+            if !seen.insert(start) {
+                continue;
+            }
+            let mut component = Vec::new();
+            let mut stack = vec![start];
+            while let Some(id) = stack.pop() {
+                component.push(id);
+                let deps = match id {
+                    CellID::Input(_) => &[][..],
+                    CellID::Compute(ComputeCellID(idx)) => &self.compute_cells[idx].deps[..],
+                };
+                let clients = self.clients(id).iter().map(|c| CellID::Compute(*c));
+                for next in deps.iter().copied().chain(clients) {
+                    if seen.insert(next) {
+                        stack.push(next);
+                    }
+                }
+            }
+            component.sort();
+            components.push(component);
+        }
+        components.sort();
+        components
+    }
+
+    // Returns a closure that computes the value of the cell as of now, however the reactor
+    // changes before it's called. The cell's inputs are captured right away, but nothing is
+    // evaluated until the closure is called, and no cached values or callbacks are touched. The
+    // closure returns None if the cell's function, or one it depends on, fails.
+    //
+    // Returns None if the cell doesn't exist.
+    pub fn value_thunk(&self, id: CellID) -> Option<impl FnOnce() -> Option<T> + 'r> {
+        if !self.contains(id) {
+            return None;
+        }
+        let mut frozen = Frozen {
+            inputs: HashMap::new(),
+            cells: HashMap::new(),
+        };
+        let mut stack = vec![id];
+        while let Some(c) = stack.pop() {
+            match c {
+                CellID::Input(iid @ InputCellID(idx)) => {
+                    frozen
+                        .inputs
+                        .insert(iid, self.input_cells[idx].value.clone());
+                }
+                CellID::Compute(cid @ ComputeCellID(idx)) => {
+                    if let Entry::Vacant(slot) = frozen.cells.entry(cid) {
+                        let cell = &self.compute_cells[idx];
+                        match (&cell.fun, cell.dirty.get(), cell.cached()) {
+                            // a fold that's current is its state; one that's stale steps from a
+                            // copy of it, so later steps don't leak in
+                            (Formula::Fold(..), false, Some(v)) => {
+                                slot.insert((Formula::Static(Rc::new(move |_| v.clone())), vec![]));
+                            }
+                            (Formula::Fold(fun, state), ..) => {
+                                let state = Rc::new(RefCell::new(state.borrow().clone()));
+                                slot.insert((
+                                    Formula::Fold(Rc::clone(fun), state),
+                                    cell.deps.clone(),
+                                ));
+                                stack.extend(cell.deps.iter().copied());
+                            }
+                            (fun, ..) => {
+                                slot.insert((fun.clone(), cell.deps.clone()));
+                                stack.extend(cell.deps.iter().copied());
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        Some(move || frozen.eval(id, &mut HashMap::new()))
+    }
+
+    // Returns the compute cells, in ID order, that don't depend on any input cell, even
+    // transitively, such as constants and cells computed only from constants. No input edit can
+    // change their value.
+    pub fn effectively_constant(&self) -> Vec<ComputeCellID> {
+        (0..self.compute_cells.len())
+            .map(ComputeCellID)
+            .filter(|id| {
+                self.contains(CellID::Compute(*id))
+                    && self.root_inputs(CellID::Compute(*id)).is_empty()
+            })
+            .collect()
+    }
+
+    // Returns the input cells a cell transitively depends on, in ID order.
+    fn root_inputs(&self, id: CellID) -> Vec<InputCellID> {
+        let mut roots = BTreeSet::new();
+        let mut seen = HashSet::new();
+        let mut stack = vec![id];
+        while let Some(c) = stack.pop() {
+            match c {
+                CellID::Input(iid) => {
+                    roots.insert(iid);
+                }
+                CellID::Compute(cid @ ComputeCellID(idx)) => {
+                    if seen.insert(cid) {
+                        stack.extend(self.compute_cells[idx].deps.iter().copied());
+                    }
+                }
+            }
+        }
+        roots.into_iter().collect()
+    }
+
+    // Returns the current generation. It starts at 0 and goes up by one on every call that changes
+    // the value of at least one input, e.g. `set_value` or `commit`.
+    pub fn current_generation(&self) -> u64 {
+        self.generation
+    }
+
+    // Returns how many times the cell's value has changed since it was created. Unlike the
+    // reactor-wide `current_generation`, it only moves when this cell's own value changes, so
+    // comparing it to an earlier reading tells whether the value has changed since.
+    //
+    // Returns None if the cell does not exist.
+    pub fn generation(&self, id: CellID) -> Option<u64> {
+        match id {
+            CellID::Input(iid) => self.input_cell(iid).map(|c| c.version),
+            CellID::Compute(cid) => self.compute_cell(cid).map(|c| c.version.get()),
+        }
+    }
+
+    // Returns every cell whose value changed since the last call, or since it was created, along
+    // with its current value: the input cells, then the compute cells, each in ID order. A cell
+    // that changed several times is reported once, with its latest value. This is a way to poll
+    // for changes rather than be called back with each one.
+    //
+    // A cell counts as changed when its `generation` moved, so one that changed and then changed
+    // back is still reported. A compute cell that's currently without a value, because its
+    // function failed, is reported once it has one again; when lazy, a stale cell is reported
+    // once it's been recomputed.
+    pub fn drain_changes(&mut self) -> Vec<(CellID, T)> {
+        let mut changes = Vec::new();
+        for id in self.cell_ids().collect::<Vec<_>>() {
+            let now = self.generation(id).unwrap_or(0);
+            if self.drained.get(&id).copied().unwrap_or(0) == now {
+                continue;
+            }
+            if let Some(value) = self.cached_value(id) {
+                self.drained.insert(id, now);
+                changes.push((id, value));
+            }
+        }
+        changes
+    }
+
+    // Retrieves the value a cell had as of a past generation, or None if the cell doesn't exist,
+    // didn't exist yet at that generation, or `generation` is in the future or more than
+    // `GENERATION_WINDOW` generations ago.
+    pub fn value_at_generation(&self, id: CellID, generation: u64) -> Option<T> {
+        if generation > self.generation || generation + GENERATION_WINDOW < self.generation {
+            return None;
+        }
+        match id {
+            CellID::Input(InputCellID(idx)) => {
+                self.input_cell(InputCellID(idx))?.history.at(generation)
+            }
+            CellID::Compute(ComputeCellID(idx)) => self
+                .compute_cell(ComputeCellID(idx))?
+                .history
+                .borrow()
+                .at(generation),
+        }
+    }
+
+    // Gives a cell a unique string key to look it up by, replacing any key it already had.
+    //
+    // Returns an Err if the cell doesn't exist or another cell already has the key.
+    pub fn set_key(&mut self, id: CellID, key: impl Into<String>) -> Result<(), KeyError> {
+        if !self.contains(id) {
+            return Err(KeyError::NonexistentCell);
+        }
+        let key = key.into();
+        match self.keys.get(&key) {
+            Some(holder) if *holder == id => return Ok(()),
+            Some(holder) => return Err(KeyError::DuplicateKey(*holder)),
+            None => {}
+        }
+        if let Some(old) = self.cell_keys.insert(id, key.clone()) {
+            self.keys.remove(&old);
+        }
+        self.keys.insert(key, id);
+        Ok(())
+    }
+
+    // Sets the input cells with the given keys to the given values, then propagates once, as
+    // `commit` does. Returns the compute cells whose value changed.
+    //
+    // If any key doesn't belong to an input cell, returns an Err with that key and sets nothing.
+    pub fn set_by_keys(
+        &mut self,
+        updates: &HashMap<String, T>,
+    ) -> Result<Vec<ComputeCellID>, KeyError> {
+        let resolved = updates
+            .iter()
+            .map(|(key, value)| match self.keys.get(key) {
+                Some(CellID::Input(id)) => Ok((*id, value.clone())),
+                _ => Err(KeyError::UnknownKey(key.clone())),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(self.apply_inputs(resolved))
+    }
+
+    fn remove_key(&mut self, id: CellID) {
+        if let Some(key) = self.cell_keys.remove(&id) {
+            self.keys.remove(&key);
+        }
+    }
+
+    // Returns the cell with the given key, if any.
+    pub fn cell_by_key(&self, key: &str) -> Option<CellID> {
+        self.keys.get(key).copied()
+    }
+
+    // Returns the key of a cell, if it has one.
+    pub fn key(&self, id: CellID) -> Option<&str> {
+        self.cell_keys.get(&id).map(String::as_str)
+    }
+
+    // Creates an input cell as `create_input` does, with `name` as its key.
+    //
+    // Returns an Err, creating nothing, if another cell already has the name.
+    pub fn create_named_input(&mut self, name: &str, initial: T) -> Result<InputCellID, KeyError> {
+        if let Some(holder) = self.cell_by_name(name) {
+            return Err(KeyError::DuplicateKey(holder));
+        }
+        let id = self.create_input(initial);
+        self.set_key(CellID::Input(id), name)?;
+        Ok(id)
+    }
+
+    // Creates a compute cell as `create_compute` does, with `name` as its key.
+    //
+    // Returns an Err, creating nothing, if another cell already has the name, or the cell can't
+    // be created.
+    pub fn create_named_compute<F>(
+        &mut self,
+        name: &str,
+        dependencies: &[CellID],
+        compute_func: F,
+    ) -> Result<ComputeCellID, CreateComputeError>
+    where
+        F: 'r + Fn(&[T]) -> T,
+    {
+        if let Some(holder) = self.cell_by_name(name) {
+            return Err(CreateComputeError::DuplicateKey(holder));
+        }
+        let id = self.create_compute(dependencies, compute_func)?;
+        let _ = self.set_key(CellID::Compute(id), name);
+        Ok(id)
+    }
+
+    // Returns the cell with the given name. A cell's name is its key, so this is the same as
+    // `cell_by_key`.
+    pub fn cell_by_name(&self, name: &str) -> Option<CellID> {
+        self.cell_by_key(name)
+    }
+
+    // Recomputes `seeds` and, transitively, the clients of every cell whose value changed,
+    // returning the cells that changed in the order they were recomputed. Branches below a cell
+    // that recomputes to its cached value are pruned.
+    //
+    // Cells are recomputed, and their callbacks fired, in order of rank (ties broken by ID). A
+    // cell's rank is higher than any of its dependencies', so each cell is recomputed at most
+    // once, after all of its dependencies are final, and upstream callbacks fire before
+    // downstream ones. A cell a callback read, and so brought up to date early, isn't recomputed
+    // again, but still counts as changed if it did.
+    fn propagate(&self, seeds: impl IntoIterator<Item = ComputeCellID>) -> Vec<ComputeCellID> {
+        let ranked = |id @ ComputeCellID(idx): ComputeCellID| (self.compute_cells[idx].rank, id);
+        let versions = self.versions();
+        let mut pending = seeds.into_iter().map(ranked).collect::<BTreeSet<_>>();
+        let mut changed = Vec::new();
+        while let Some((_, id)) = pending.pop_first() {
+            let ComputeCellID(idx) = id;
+            let cell = &self.compute_cells[idx];
+            if cell.refresh_since(self, versions[idx]) {
+                pending.extend(cell.clients.iter().copied().map(ranked));
+                changed.push(id);
+            }
+        }
+        changed
+    }
+
+    // Returns every compute cell's version, indexed by ID.
+    fn versions(&self) -> Vec<u64> {
+        self.compute_cells.iter().map(|c| c.version.get()).collect()
+    }
+
+    // Returns the current value of an input cell, or the cached value of a compute cell, without
+    // evaluating anything.
+    fn cached_value(&self, id: CellID) -> Option<T> {
+        match id {
+            CellID::Input(InputCellID(idx)) => {
+                self.input_cell(InputCellID(idx)).map(|i| i.value.clone())
+            }
+            CellID::Compute(ComputeCellID(idx)) => self
+                .compute_cell(ComputeCellID(idx))
+                .and_then(|c| c.cached()),
+        }
+    }
+
+    // Evaluates every compute cell twice, back to back and without any intervening input change,
+    // and returns the cells whose two results differ. Meant as a test aid for catching compute
+    // functions that aren't pure.
+    //
+    // Neither pass touches the cached values or fires callbacks. Cells downstream of an impure
+    // cell will usually be reported as well, since their own inputs differ between the passes.
+    pub fn detect_impure(&self) -> Vec<ComputeCellID> {
+        let mut first = Scratch::new();
+        let mut second = Scratch::new();
+        (0..self.compute_cells.len())
+            .map(ComputeCellID)
+            .filter(|&id| {
+                self.eval_scratch(CellID::Compute(id), &mut first)
+                    != self.eval_scratch(CellID::Compute(id), &mut second)
+            })
+            .collect()
+    }
+
+    // Returns whether `cell` is a fold that has already folded in the values its dependencies
+    // have in `scratch`, in which case its current value is its state rather than the next step.
+    fn fold_is_settled(&self, cell: &ComputeCell<'r, T>, scratch: &mut Scratch<'_, T>) -> bool {
+        if !matches!(cell.fun, Formula::Fold(..)) || cell.dirty.get() {
+            return false;
+        }
+        cell.deps.iter().all(|d| {
+            let v = self.eval_scratch(*d, scratch);
+            v.is_some() && v == self.cached_value(*d)
+        })
+    }
+
+    // Computes the value of a cell without consulting or updating any compute cell's cached
+    // value, and without firing callbacks. Each compute cell is evaluated at most once per
+    // `scratch`, so shared dependencies are only computed once.
+    fn eval_scratch(&self, id: CellID, scratch: &mut Scratch<'_, T>) -> Option<T> {
+        match id {
+            CellID::Input(iid) => {
+                let cell = self.input_cell(iid)?;
+                let v = scratch
+                    .inputs
+                    .get(&iid)
+                    .cloned()
+                    .unwrap_or_else(|| cell.value.clone());
+                if let Some(trace) = &mut scratch.trace {
+                    if !trace.iter().any(|(c, _)| *c == id) {
+                        trace.push((id, v.clone()));
+                    }
+                }
+                Some(v)
+            }
+            CellID::Compute(cid) => {
+                if let Some(v) = scratch.memo.get(&cid) {
+                    return Some(v.clone());
+                }
+                let ComputeCellID(idx) = cid;
+                let cell = self.compute_cell(ComputeCellID(idx))?;
+                let v = match scratch.formula {
+                    Some((overridden, fun)) if overridden == cid => {
+                        let deps = cell
+                            .deps
+                            .iter()
+                            .map(|d| self.eval_scratch(*d, scratch))
+                            .collect::<Option<Vec<T>>>()?;
+                        fun(&deps)
+                    }
+                    _ => {
+                        if self.fold_is_settled(cell, scratch) {
+                            cell.cached()?
+                        } else {
+                            // the formula reads through a shared closure, so lend it the scratch
+                            // space for the duration
+                            let shared = RefCell::new(std::mem::replace(scratch, Scratch::new()));
+                            let v = cell.evaluate(
+                                &|d| self.eval_scratch(d, &mut shared.borrow_mut()),
+                                false,
+                            );
+                            *scratch = shared.into_inner();
+                            v?
+                        }
+                    }
+                };
+                scratch.memo.insert(cid, v.clone());
+                if let Some(trace) = &mut scratch.trace {
+                    trace.push((id, v.clone()));
+                }
+                Some(v)
+            }
+        }
+    }
+}
+
+impl<'r, T: Clone + Debug + PartialEq + Into<f64> + From<f64> + 'r> Reactor<'r, T> {
+    // Creates a compute cell holding the weighted average of `deps`. The weights are normalized
+    // to sum to one, so `[(a, 1.0), (b, 3.0)]` and `[(a, 0.25), (b, 0.75)]` are equivalent.
+    //
+    // Returns an Err if a dependency doesn't exist, if a weight isn't finite, or if the weights
+    // sum to zero.
+    pub fn create_weighted_average(
+        &mut self,
+        deps: &[(CellID, f64)],
+    ) -> Result<ComputeCellID, CreateComputeError> {
+        if let Some((id, _)) = deps.iter().find(|(_, w)| !w.is_finite()) {
+            return Err(CreateComputeError::NonFiniteWeight(*id));
+        }
+        let total: f64 = deps.iter().map(|(_, w)| w).sum();
+        if total == 0.0 {
+            return Err(CreateComputeError::ZeroTotalWeight);
+        }
+        let ids = deps.iter().map(|(id, _)| *id).collect::<Vec<_>>();
+        let weights = deps.iter().map(|(_, w)| w / total).collect::<Vec<_>>();
+        self.create_compute(&ids, move |v| {
+            let avg: f64 = v
+                .iter()
+                .zip(weights.iter())
+                .map(|(x, w)| w * x.clone().into())
+                .sum();
+            T::from(avg)
+        })
+    }
+}
+
+impl<'r, T: Clone + Debug + PartialEq + Add<Output = T> + Sub<Output = T> + 'r> Reactor<'r, T> {
+    // Estimates how much each input a compute cell depends on contributes to its value: each
+    // input is raised by `epsilon` in its own scratch evaluation, and the resulting change in the
+    // cell's value is paired with the input's ID. Divide by `epsilon` for a finite-difference
+    // derivative.
+    //
+    // Returns an empty Vec if the cell doesn't exist.
+    pub fn sensitivities(&self, id: ComputeCellID, epsilon: T) -> Vec<(InputCellID, T)> {
+        if !self.contains(CellID::Compute(id)) {
+            return Vec::new();
+        }
+        let base = self.eval_scratch(CellID::Compute(id), &mut Scratch::new());
+        self.root_inputs(CellID::Compute(id))
+            .into_iter()
+            .filter_map(|iid @ InputCellID(i)| {
+                let mut scratch = Scratch::new();
+                scratch
+                    .inputs
+                    .insert(iid, self.input_cells[i].value.clone() + epsilon.clone());
+                let perturbed = self.eval_scratch(CellID::Compute(id), &mut scratch)?;
+                Some((iid, perturbed - base.clone()?))
+            })
+            .collect()
+    }
+}
+
+impl<'r, T: Clone + Debug + PartialEq + Nudge + 'r> Reactor<'r, T> {
+    // Returns the inputs a compute cell currently responds to: those whose value, nudged one unit
+    // up or down in a scratch evaluation, changes the cell's value. Inputs the cell depends on
+    // but is currently insensitive to, like the unselected branch of a select, are left out. A
+    // nudge that would overflow, like an unsigned zero nudged down, isn't tried.
+    //
+    // Returns an empty Vec if the cell doesn't exist.
+    pub fn minimal_affecting_inputs(&self, id: ComputeCellID) -> Vec<InputCellID> {
+        if !self.contains(CellID::Compute(id)) {
+            return Vec::new();
+        }
+        let cell = CellID::Compute(id);
+        let base = self.eval_scratch(cell, &mut Scratch::new());
+        self.root_inputs(cell)
+            .into_iter()
+            .filter(|iid @ InputCellID(i)| {
+                let value = &self.input_cells[*i].value;
+                let nudges = [value.nudged_up(), value.nudged_down()];
+                nudges.iter().flatten().any(|nudged| {
+                    let mut scratch = Scratch::new();
+                    scratch.inputs.insert(*iid, nudged.clone());
+                    self.eval_scratch(cell, &mut scratch) != base
+                })
+            })
+            .collect()
+    }
+}
+
+impl<'r, T: Clone + Debug + PartialEq + Hash + 'r> Reactor<'r, T> {
+    // Returns a hash of the cell's current value. The hasher is fixed rather than randomly
+    // seeded, so equal values hash equally across processes and runs.
+    //
+    // Returns None if the cell does not exist.
+    pub fn value_hash(&self, id: CellID) -> Option<u64> {
+        let mut hasher = StableHasher::new();
+        self.value(id)?.hash(&mut hasher);
+        Some(hasher.finish())
+    }
+}
+
+// Quotes and escapes `s` as a JSON string.
+fn json_string(s: &str) -> String {
+    let mut out = String::from('"');
+    for c in s.chars() {
+        match c {
+            '"' => out += "\\\"",
+            '\\' => out += "\\\\",
+            '\n' => out += "\\n",
+            '\r' => out += "\\r",
+            '\t' => out += "\\t",
+            c if (c as u32) < 0x20 => out += &format!("\\u{:04x}", c as u32),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+// Returns `s` as it is if it's already a JSON number or literal, and as a JSON string otherwise.
+fn json_scalar(s: &str) -> String {
+    let literal = s == "true" || s == "false" || s == "null";
+    if literal || is_json_number(s) {
+        s.to_string()
+    } else {
+        json_string(s)
+    }
+}
+
+// Whether `s` follows JSON's number grammar: an optional minus sign, an integer part without
+// leading zeros, then optionally a fraction and an exponent, each with at least one digit.
+fn is_json_number(s: &str) -> bool {
+    fn digits(s: &str) -> (&str, &str) {
+        let end = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+        s.split_at(end)
+    }
+    let (int, rest) = digits(s.strip_prefix('-').unwrap_or(s));
+    if int.is_empty() || (int.len() > 1 && int.starts_with('0')) {
+        return false;
+    }
+    let rest = match rest.strip_prefix('.') {
+        Some(frac) => match digits(frac) {
+            ("", _) => return false,
+            (_, rest) => rest,
+        },
+        None => rest,
+    };
+    match rest.strip_prefix(|c| c == 'e' || c == 'E') {
+        Some(exp) => {
+            let exp = exp.strip_prefix(|c| c == '+' || c == '-').unwrap_or(exp);
+            matches!(digits(exp), (d, "") if !d.is_empty())
         }
+        None => rest.is_empty(),
     }
 }
@@ -0,0 +1,239 @@
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::sync::{Mutex, RwLock};
+
+use crate::{
+    CallbackID, CellID, ComputeCellID, CreateComputeError, InputCellID, RemoveCallbackError,
+};
+
+type SyncComputeFn<'r, T> = Box<dyn 'r + Fn(&[T]) -> T + Send + Sync>;
+type SyncCallback<'r, T> = Box<dyn 'r + FnMut(T) + Send>;
+
+/// `SyncReactor` is a reactor that can be shared between threads, e.g. behind an `Arc`. It
+/// supports input cells, compute cells and callbacks, with the same semantics as `Reactor`, but
+/// none of `Reactor`'s other features.
+///
+/// Every method takes `&self`. The graph sits behind a read-write lock: `value` takes the read
+/// lock, so any number of threads can read at once, while creating cells and `set_value` take the
+/// write lock, so readers see a value either from before a change has propagated or after, never
+/// halfway through. Callbacks are kept behind a separate mutex, which `set_value` takes before
+/// releasing the write lock and holds while firing them, so callbacks from different changes
+/// never interleave. A callback may call `value`, but calling any other method from a callback
+/// deadlocks.
+pub struct SyncReactor<'r, T> {
+    graph: RwLock<Graph<'r, T>>,
+    callbacks: Mutex<Vec<Callbacks<'r, T>>>, // indexed like the graph's compute cells
+}
+
+struct Graph<'r, T> {
+    inputs: Vec<SyncInputCell<T>>,
+    computes: Vec<SyncComputeCell<'r, T>>,
+}
+
+struct SyncInputCell<T> {
+    value: T,
+    clients: HashSet<ComputeCellID>,
+}
+
+struct SyncComputeCell<'r, T> {
+    fun: SyncComputeFn<'r, T>,
+    deps: Vec<CellID>,
+    value: T,
+    clients: HashSet<ComputeCellID>,
+    rank: usize, // longest distance from any input
+}
+
+struct Callbacks<'r, T> {
+    funs: BTreeMap<CallbackID, SyncCallback<'r, T>>,
+    next_cbid: usize, // increases monotonically; increments on adding a callback
+}
+
+impl<'r, T: Clone> Graph<'r, T> {
+    fn value(&self, id: CellID) -> Option<T> {
+        match id {
+            CellID::Input(InputCellID(idx)) => self.inputs.get(idx).map(|c| c.value.clone()),
+            CellID::Compute(ComputeCellID(idx)) => self.computes.get(idx).map(|c| c.value.clone()),
+        }
+    }
+
+    fn rank(&self, id: CellID) -> usize {
+        match id {
+            CellID::Input(_) => 0,
+            CellID::Compute(ComputeCellID(idx)) => self.computes[idx].rank,
+        }
+    }
+
+    fn evaluate(&self, fun: &SyncComputeFn<'r, T>, deps: &[CellID]) -> T {
+        let args = deps
+            .iter()
+            .map(|d| self.value(*d).expect("dependencies outlive their clients"))
+            .collect::<Vec<_>>();
+        fun(&args)
+    }
+}
+
+impl<'r, T: Clone + PartialEq + Send + Sync> SyncReactor<'r, T> {
+    pub fn new() -> Self {
+        SyncReactor {
+            graph: RwLock::new(Graph {
+                inputs: Vec::new(),
+                computes: Vec::new(),
+            }),
+            callbacks: Mutex::new(Vec::new()),
+        }
+    }
+
+    // Creates an input cell with the specified initial value, returning its ID.
+    pub fn create_input(&self, initial: T) -> InputCellID {
+        let mut graph = self.graph.write().expect("reactor lock poisoned");
+        graph.inputs.push(SyncInputCell {
+            value: initial,
+            clients: HashSet::new(),
+        });
+        InputCellID(graph.inputs.len() - 1)
+    }
+
+    // Creates a compute cell with the specified dependencies and compute function, as
+    // `Reactor::create_compute` does.
+    //
+    // If any dependency doesn't exist, returns `Err(NonexistentDependency)` with that nonexistent
+    // dependency.
+    pub fn create_compute<F>(
+        &self,
+        dependencies: &[CellID],
+        compute_func: F,
+    ) -> Result<ComputeCellID, CreateComputeError>
+    where
+        F: 'r + Fn(&[T]) -> T + Send + Sync,
+    {
+        let mut graph = self.graph.write().expect("reactor lock poisoned");
+        if let Some(id) = dependencies.iter().find(|id| graph.value(**id).is_none()) {
+            return Err(CreateComputeError::NonexistentDependency(*id));
+        }
+        let cid = ComputeCellID(graph.computes.len());
+        for id in dependencies {
+            match *id {
+                CellID::Input(InputCellID(idx)) => graph.inputs[idx].clients.insert(cid),
+                CellID::Compute(ComputeCellID(idx)) => graph.computes[idx].clients.insert(cid),
+            };
+        }
+        let fun: SyncComputeFn<'r, T> = Box::new(compute_func);
+        let value = graph.evaluate(&fun, dependencies);
+        let rank = 1 + dependencies
+            .iter()
+            .map(|d| graph.rank(*d))
+            .max()
+            .unwrap_or(0);
+        graph.computes.push(SyncComputeCell {
+            fun,
+            deps: dependencies.to_vec(),
+            value,
+            clients: HashSet::new(),
+            rank,
+        });
+        self.callbacks
+            .lock()
+            .expect("reactor lock poisoned")
+            .push(Callbacks {
+                funs: BTreeMap::new(),
+                next_cbid: 0,
+            });
+        Ok(cid)
+    }
+
+    // Retrieves the current value of the cell, or None if the cell does not exist.
+    pub fn value(&self, id: CellID) -> Option<T> {
+        self.graph.read().expect("reactor lock poisoned").value(id)
+    }
+
+    // Sets the value of the specified input cell, then propagates the change and fires the
+    // callbacks of every compute cell whose value changed, once each, with its final value.
+    //
+    // Returns false if the cell does not exist.
+    pub fn set_value(&self, id: InputCellID, new_value: T) -> bool {
+        let mut graph = self.graph.write().expect("reactor lock poisoned");
+        let InputCellID(idx) = id;
+        let cell = match graph.inputs.get_mut(idx) {
+            Some(cell) => cell,
+            None => return false,
+        };
+        if cell.value == new_value {
+            return true;
+        }
+        cell.value = new_value;
+        let clients = cell.clients.iter().copied().collect::<Vec<_>>();
+
+        // recompute in order of rank, so each cell is recomputed after all its dependencies
+        let mut pending = clients
+            .into_iter()
+            .map(|c| (graph.rank(CellID::Compute(c)), c))
+            .collect::<BTreeSet<_>>();
+        let mut changed = Vec::new();
+        while let Some((_, cid @ ComputeCellID(cidx))) = pending.pop_first() {
+            let cell = &graph.computes[cidx];
+            let nv = graph.evaluate(&cell.fun, &cell.deps);
+            if nv != cell.value {
+                for &client @ ComputeCellID(idx) in &cell.clients {
+                    pending.insert((graph.computes[idx].rank, client));
+                }
+                graph.computes[cidx].value = nv;
+                changed.push(cid);
+            }
+        }
+
+        let mut callbacks = self.callbacks.lock().expect("reactor lock poisoned");
+        let values = changed
+            .into_iter()
+            .map(|ComputeCellID(cidx)| (cidx, graph.computes[cidx].value.clone()))
+            .collect::<Vec<_>>();
+        drop(graph);
+        for (cidx, value) in values {
+            for callback in callbacks[cidx].funs.values_mut() {
+                callback(value.clone());
+            }
+        }
+        true
+    }
+
+    // Adds a callback to the specified compute cell, with the same semantics as
+    // `Reactor::add_callback`.
+    //
+    // Returns the ID of the just-added callback, or None if the cell doesn't exist.
+    pub fn add_callback<F: 'r + FnMut(T) + Send>(
+        &self,
+        id: ComputeCellID,
+        callback: F,
+    ) -> Option<CallbackID> {
+        let ComputeCellID(idx) = id;
+        let mut callbacks = self.callbacks.lock().expect("reactor lock poisoned");
+        let cell = callbacks.get_mut(idx)?;
+        let cbid = CallbackID(cell.next_cbid);
+        cell.next_cbid += 1;
+        cell.funs.insert(cbid, Box::new(callback));
+        Some(cbid)
+    }
+
+    // Removes the specified callback, using an ID returned from add_callback.
+    //
+    // Returns an Err if either the cell or callback does not exist.
+    pub fn remove_callback(
+        &self,
+        cell: ComputeCellID,
+        callback: CallbackID,
+    ) -> Result<(), RemoveCallbackError> {
+        let ComputeCellID(idx) = cell;
+        let mut callbacks = self.callbacks.lock().expect("reactor lock poisoned");
+        let cell = callbacks
+            .get_mut(idx)
+            .ok_or(RemoveCallbackError::NonexistentCell)?;
+        cell.funs
+            .remove(&callback)
+            .map(|_| ())
+            .ok_or(RemoveCallbackError::NonexistentCallback)
+    }
+}
+
+impl<'r, T: Clone + PartialEq + Send + Sync> Default for SyncReactor<'r, T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -51,7 +51,9 @@ fn error_creating_compute_cell_if_input_doesnt_exist() {
     let input = dummy_reactor.create_input(1);
     assert_eq!(
         Reactor::new().create_compute(&[CellID::Input(input)], |_| 0),
-        Err(CellID::Input(input))
+        Err(CreateComputeError::NonexistentDependency(CellID::Input(
+            input
+        )))
     );
 }
 
@@ -64,7 +66,9 @@ fn do_not_break_cell_if_creating_compute_cell_with_valid_and_invalid_input() {
     let input = reactor.create_input(1);
     assert_eq!(
         reactor.create_compute(&[CellID::Input(input), CellID::Input(dummy_cell)], |_| 0),
-        Err(CellID::Input(dummy_cell))
+        Err(CreateComputeError::NonexistentDependency(CellID::Input(
+            dummy_cell
+        )))
     );
     assert!(reactor.set_value(input, 5));
     assert_eq!(reactor.value(CellID::Input(input)), Some(5));
@@ -417,3 +421,3648 @@ fn test_adder_with_boolean_values() {
         );
     }
 }
+
+#[test]
+fn detect_impure_flags_only_impure_cells() {
+    let calls = std::cell::Cell::new(0);
+    let mut reactor = Reactor::new();
+    let input = reactor.create_input(1);
+    let pure = reactor
+        .create_compute(&[CellID::Input(input)], |v| v[0] * 2)
+        .unwrap();
+    let impure = reactor
+        .create_compute(&[CellID::Input(input)], |v| {
+            calls.set(calls.get() + 1);
+            v[0] + calls.get()
+        })
+        .unwrap();
+    assert_eq!(reactor.detect_impure(), vec![impure]);
+    assert!(!reactor.detect_impure().contains(&pure));
+}
+
+#[test]
+fn reactor_macro_matches_manual_construction() -> Result<(), CreateComputeError> {
+    let mut manual = Reactor::new();
+    let ma = manual.create_input(1);
+    let mb = manual.create_input(2);
+    let mc = manual.create_compute(&[CellID::Input(ma), CellID::Input(mb)], |v| v[0] + v[1])?;
+    let md = manual.create_compute(&[CellID::Compute(mc), CellID::Input(ma)], |v| v[0] * v[1])?;
+
+    let mut r = Reactor::new();
+    reactor! { r;
+        a = input(1);
+        b = input(2);
+        c = compute(a, b => |x, y| x + y);
+        d = compute(c, a => |x, y| x * y);
+    }
+
+    assert_eq!(
+        r.value(CellID::Compute(c)),
+        manual.value(CellID::Compute(mc))
+    );
+    assert_eq!(
+        r.value(CellID::Compute(d)),
+        manual.value(CellID::Compute(md))
+    );
+    assert!(r.set_value(a, 5) && manual.set_value(ma, 5));
+    assert!(r.set_value(b, 7) && manual.set_value(mb, 7));
+    assert_eq!(r.value(CellID::Compute(d)), Some(60));
+    assert_eq!(
+        r.value(CellID::Compute(d)),
+        manual.value(CellID::Compute(md))
+    );
+    Ok(())
+}
+
+#[test]
+fn reactor_macro_allows_a_repeated_dependency() -> Result<(), CreateComputeError> {
+    let mut r = Reactor::new();
+    reactor! { r;
+        a = input(3);
+        b = input(4);
+        sq = compute(a, a => |x, y| x * y);
+        mixed = compute(b, a, b => |x, y, z| x * 100 + y * 10 + z);
+    }
+    assert_eq!(r.value(CellID::Compute(sq)), Some(9));
+    assert_eq!(r.value(CellID::Compute(mixed)), Some(434));
+    assert!(r.set_value(a, 5));
+    assert_eq!(r.value(CellID::Compute(sq)), Some(25));
+    Ok(())
+}
+
+#[test]
+fn reactor_macro_surfaces_creation_errors() {
+    fn build(r: &mut Reactor<i32>, a: InputCellID) -> Result<ComputeCellID, CreateComputeError> {
+        reactor! { r;
+            b = compute(a => |x| x + 1);
+        }
+        Ok(b)
+    }
+    let mut dummy = Reactor::new();
+    let a = dummy.create_input(1);
+    assert_eq!(
+        build(&mut Reactor::new(), a),
+        Err(CreateComputeError::NonexistentDependency(CellID::Input(a)))
+    );
+    assert!(build(&mut dummy, a).is_ok());
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn snapshot_log_round_trips_and_replays() {
+    let mut reactor = Reactor::new();
+    let a = reactor.create_input(1);
+    let b = reactor.create_input(2);
+    let sum = reactor
+        .create_compute(&[CellID::Input(a), CellID::Input(b)], |v| v[0] + v[1])
+        .unwrap();
+
+    let mut log = SnapshotLog::new();
+    reactor.record(&mut log);
+    reactor.set_value(a, 10);
+    reactor.record(&mut log);
+    reactor.set_value(b, 20);
+    reactor.record(&mut log);
+
+    let json = serde_json::to_string(&log).unwrap();
+    let restored: SnapshotLog<i32> = serde_json::from_str(&json).unwrap();
+    assert_eq!(restored, log);
+    assert_eq!(restored.len(), 3);
+
+    let mut rebuilt = Reactor::new();
+    let a = rebuilt.create_input(1);
+    let b = rebuilt.create_input(2);
+    let sum2 = rebuilt
+        .create_compute(&[CellID::Input(a), CellID::Input(b)], |v| v[0] + v[1])
+        .unwrap();
+    assert!(rebuilt.replay_log(&restored));
+    assert_eq!(rebuilt.value(CellID::Input(a)), Some(10));
+    assert_eq!(rebuilt.value(CellID::Input(b)), Some(20));
+    assert_eq!(
+        rebuilt.value(CellID::Compute(sum2)),
+        reactor.value(CellID::Compute(sum))
+    );
+}
+
+#[test]
+fn replaying_a_log_applies_each_state_in_one_step() {
+    let cb = CallbackRecorder::new();
+    let mut reactor = Reactor::new();
+    let a = reactor.create_input(1);
+    let b = reactor.create_input(2);
+    let sum = reactor
+        .create_compute(&[CellID::Input(a), CellID::Input(b)], |v| v[0] + v[1])
+        .unwrap();
+    assert!(reactor
+        .add_callback(sum, |v| cb.callback_called(v))
+        .is_some());
+
+    let mut log = SnapshotLog::new();
+    let mut source = Reactor::new();
+    let x = source.create_input(2);
+    let y = source.create_input(1);
+    source.record(&mut log);
+    source.set_value(x, 5);
+    source.set_value(y, 5);
+    source.record(&mut log);
+
+    assert!(reactor.replay_log(&log));
+    cb.expect_to_have_been_called_with(10);
+    assert_eq!(reactor.value(CellID::Compute(sum)), Some(10));
+}
+
+#[test]
+fn replay_log_rejects_mismatched_states() {
+    let mut source = Reactor::new();
+    source.create_input(1);
+    source.create_input(2);
+    let mut log = SnapshotLog::new();
+    source.record(&mut log);
+
+    let mut reactor = Reactor::new();
+    let a = reactor.create_input(5);
+    assert!(!reactor.replay_log(&log));
+    assert_eq!(reactor.value(CellID::Input(a)), Some(5));
+}
+
+#[test]
+fn weighted_average_normalizes_weights() {
+    let mut reactor = Reactor::new();
+    let a = reactor.create_input(4.0);
+    let b = reactor.create_input(8.0);
+    let avg = reactor
+        .create_weighted_average(&[(CellID::Input(a), 0.25), (CellID::Input(b), 0.75)])
+        .unwrap();
+    assert_eq!(reactor.value(CellID::Compute(avg)), Some(7.0));
+    let scaled = reactor
+        .create_weighted_average(&[(CellID::Input(a), 1.0), (CellID::Input(b), 3.0)])
+        .unwrap();
+    assert!(reactor.set_value(a, 0.0));
+    assert_eq!(reactor.value(CellID::Compute(avg)), Some(6.0));
+    assert_eq!(reactor.value(CellID::Compute(scaled)), Some(6.0));
+}
+
+#[test]
+fn weighted_average_validates_dependencies_and_weights() {
+    let mut dummy = Reactor::new();
+    let missing = dummy.create_input(1.0);
+    let mut reactor = Reactor::<f64>::new();
+    assert_eq!(
+        reactor.create_weighted_average(&[(CellID::Input(missing), 1.0)]),
+        Err(CreateComputeError::NonexistentDependency(CellID::Input(
+            missing
+        )))
+    );
+    let a = reactor.create_input(1.0);
+    assert_eq!(
+        reactor.create_weighted_average(&[(CellID::Input(a), f64::NAN)]),
+        Err(CreateComputeError::NonFiniteWeight(CellID::Input(a)))
+    );
+    assert_eq!(
+        reactor.create_weighted_average(&[(CellID::Input(a), 0.0)]),
+        Err(CreateComputeError::ZeroTotalWeight)
+    );
+}
+
+#[test]
+fn muted_callbacks_do_not_fire_but_values_update() {
+    let cb = CallbackRecorder::new();
+    let mut reactor = Reactor::new();
+    let input = reactor.create_input(1);
+    let output = reactor
+        .create_compute(&[CellID::Input(input)], |v| v[0] + 1)
+        .unwrap();
+    assert!(reactor
+        .add_callback(output, |v| cb.callback_called(v))
+        .is_some());
+
+    reactor.mute_callbacks();
+    assert!(reactor.set_value(input, 2));
+    assert_eq!(reactor.value(CellID::Compute(output)), Some(3));
+    cb.expect_not_to_have_been_called();
+
+    reactor.unmute_callbacks();
+    cb.expect_not_to_have_been_called();
+    assert!(reactor.set_value(input, 3));
+    cb.expect_to_have_been_called_with(4);
+}
+
+#[test]
+fn propagation_skips_branches_below_unchanged_cells() {
+    let below_calls = std::cell::Cell::new(0);
+    let mut reactor = Reactor::new();
+    let a = reactor.create_input(50);
+    let clamped = reactor
+        .create_compute(&[CellID::Input(a)], |v| v[0].max(100))
+        .unwrap();
+    let below = reactor
+        .create_compute(&[CellID::Compute(clamped)], |v| {
+            below_calls.set(below_calls.get() + 1);
+            v[0] * 2
+        })
+        .unwrap();
+    let calls_after_creation = below_calls.get();
+
+    assert!(reactor.set_value(a, 60));
+    assert_eq!(below_calls.get(), calls_after_creation);
+
+    assert!(reactor.set_value(a, 150));
+    assert_eq!(below_calls.get(), calls_after_creation + 1);
+    assert_eq!(reactor.value(CellID::Compute(below)), Some(300));
+}
+
+#[test]
+fn dependency_values_pairs_ids_with_values_in_order() {
+    let mut reactor = Reactor::new();
+    let a = reactor.create_input(1);
+    let b = reactor.create_input(2);
+    let double = reactor
+        .create_compute(&[CellID::Input(a)], |v| v[0] * 2)
+        .unwrap();
+    let deps = [CellID::Input(b), CellID::Compute(double), CellID::Input(a)];
+    let output = reactor
+        .create_compute(&deps, |v| v[0] + v[1] + v[2])
+        .unwrap();
+    assert!(reactor.set_value(a, 5));
+    assert_eq!(
+        reactor.dependency_values(output),
+        Some(vec![
+            (CellID::Input(b), 2),
+            (CellID::Compute(double), 10),
+            (CellID::Input(a), 5),
+        ])
+    );
+    assert_eq!(Reactor::<i32>::new().dependency_values(output), None);
+}
+
+#[test]
+fn staged_values_apply_on_commit_with_one_propagation() {
+    let sum_calls = std::cell::Cell::new(0);
+    let mut reactor = Reactor::new();
+    let a = reactor.create_input(1);
+    let b = reactor.create_input(2);
+    let sum = reactor
+        .create_compute(&[CellID::Input(a), CellID::Input(b)], |v| {
+            sum_calls.set(sum_calls.get() + 1);
+            v[0] + v[1]
+        })
+        .unwrap();
+    let calls_after_creation = sum_calls.get();
+
+    assert!(reactor.stage_value(a, 10));
+    assert!(reactor.stage_value(b, 20));
+    assert_eq!(reactor.staged_value(a), Some(10));
+    assert_eq!(reactor.staged_value(b), Some(20));
+    assert_eq!(reactor.value(CellID::Input(a)), Some(1));
+
+    assert_eq!(reactor.commit(), vec![sum]);
+    assert_eq!(sum_calls.get(), calls_after_creation + 1);
+    assert_eq!(reactor.staged_value(a), None);
+    assert_eq!(reactor.value(CellID::Compute(sum)), Some(30));
+}
+
+#[test]
+fn rollback_discards_staged_values() {
+    let mut reactor = Reactor::new();
+    let a = reactor.create_input(1);
+    assert!(reactor.stage_value(a, 10));
+    reactor.rollback();
+    assert_eq!(reactor.staged_value(a), None);
+    assert!(reactor.commit().is_empty());
+    assert_eq!(reactor.value(CellID::Input(a)), Some(1));
+    assert!(!Reactor::new().stage_value(a, 0));
+}
+
+#[test]
+fn most_recently_changed_tracks_the_latest_change() {
+    let mut reactor = Reactor::new();
+    let a = reactor.create_input(1);
+    let b = reactor.create_input(1);
+    let from_a = reactor
+        .create_compute(&[CellID::Input(a)], |v| v[0] + 1)
+        .unwrap();
+    let from_b = reactor
+        .create_compute(&[CellID::Input(b)], |v| v[0] + 1)
+        .unwrap();
+    assert_eq!(reactor.most_recently_changed(), None);
+
+    assert!(reactor.set_value(b, 2));
+    assert_eq!(reactor.most_recently_changed(), Some(from_b));
+    assert!(reactor.set_value(a, 2));
+    assert_eq!(reactor.most_recently_changed(), Some(from_a));
+    assert!(reactor.set_value(a, 2));
+    assert_eq!(reactor.most_recently_changed(), Some(from_a));
+    assert!(reactor.set_value(b, 3));
+    assert_eq!(reactor.most_recently_changed(), Some(from_b));
+}
+
+#[test]
+fn cell_ranks_layer_a_diamond() {
+    let mut reactor = Reactor::new();
+    let input = reactor.create_input(1);
+    let left = reactor
+        .create_compute(&[CellID::Input(input)], |v| v[0] + 1)
+        .unwrap();
+    let right = reactor
+        .create_compute(&[CellID::Input(input)], |v| v[0] - 1)
+        .unwrap();
+    let output = reactor
+        .create_compute(&[CellID::Compute(left), CellID::Compute(right)], |v| {
+            v[0] * v[1]
+        })
+        .unwrap();
+    let ranks = reactor.cell_ranks();
+    assert_eq!(ranks.len(), 4);
+    assert_eq!(ranks[&CellID::Input(input)], 0);
+    assert_eq!(ranks[&CellID::Compute(left)], 1);
+    assert_eq!(ranks[&CellID::Compute(right)], 1);
+    assert_eq!(ranks[&CellID::Compute(output)], 2);
+}
+
+#[test]
+fn take_callbacks_fire_a_limited_number_of_times() {
+    let fired = std::cell::RefCell::new(Vec::new());
+    let mut reactor = Reactor::new();
+    let input = reactor.create_input(1);
+    let output = reactor
+        .create_compute(&[CellID::Input(input)], |v| v[0] + 1)
+        .unwrap();
+    let callback = reactor
+        .add_callback_take(output, 2, |v| fired.borrow_mut().push(v))
+        .unwrap();
+
+    for i in 2..6 {
+        assert!(reactor.set_value(input, i));
+    }
+    assert_eq!(*fired.borrow(), vec![3, 4]);
+    assert_eq!(
+        reactor.remove_callback(output, callback),
+        Err(RemoveCallbackError::NonexistentCallback)
+    );
+}
+
+#[test]
+fn topology_diff_reports_edge_changes() {
+    let mut original = Reactor::new();
+    let a = original.create_input(1);
+    let b = original.create_input(2);
+    let out = original
+        .create_compute(&[CellID::Input(a)], |v| v[0])
+        .unwrap();
+
+    let mut refactored = Reactor::new();
+    refactored.create_input(1);
+    refactored.create_input(2);
+    refactored
+        .create_compute(&[CellID::Input(a), CellID::Input(b)], |v| v[0] + v[1])
+        .unwrap();
+    let extra = refactored
+        .create_compute(&[CellID::Compute(out)], |v| v[0])
+        .unwrap();
+
+    let diff = original.topology_diff(&refactored);
+    assert_eq!(
+        diff.added_edges,
+        vec![(CellID::Input(b), out), (CellID::Compute(out), extra)]
+    );
+    assert!(diff.removed_edges.is_empty());
+    assert_eq!(diff.input_delta, 0);
+    assert_eq!(diff.compute_delta, 1);
+
+    let back = refactored.topology_diff(&original);
+    assert_eq!(back.removed_edges, diff.added_edges);
+    assert_eq!(back.compute_delta, -1);
+}
+
+#[test]
+fn topology_diff_does_not_count_removed_cells() {
+    let mut original = Reactor::new();
+    let a = original.create_input(1);
+    original.map1(a, |x| x + 1).unwrap();
+
+    let mut pruned = Reactor::new();
+    let a = pruned.create_input(1);
+    let b = pruned.create_input(2);
+    pruned.map1(a, |x| x + 1).unwrap();
+    let extra = pruned.map1(b, |x| x * 2).unwrap();
+    assert_eq!(pruned.remove_compute(extra), Ok(()));
+    assert_eq!(pruned.remove_input(b), Ok(()));
+
+    let diff = original.topology_diff(&pruned);
+    assert_eq!(diff.input_delta, 0);
+    assert_eq!(diff.compute_delta, 0);
+    assert!(diff.added_edges.is_empty() && diff.removed_edges.is_empty());
+}
+
+#[test]
+fn cache_policy_always_reads_the_cache() {
+    let calls = std::cell::Cell::new(0);
+    let mut reactor = Reactor::new();
+    let input = reactor.create_input(1);
+    let output = reactor
+        .create_compute(&[CellID::Input(input)], |v| {
+            calls.set(calls.get() + 1);
+            v[0] * 10
+        })
+        .unwrap();
+    let calls_after_creation = calls.get();
+    let cached = reactor.value_with_policy(CellID::Compute(output), CachePolicy::Always);
+    assert_eq!(cached, Some(10));
+    assert_eq!(calls.get(), calls_after_creation);
+    assert_eq!(
+        reactor.value_with_policy(CellID::Input(input), CachePolicy::Always),
+        Some(1)
+    );
+}
+
+#[test]
+fn cache_policy_never_leaves_the_cache_alone() {
+    let calls = std::cell::Cell::new(0);
+    let mut reactor = Reactor::new();
+    let input = reactor.create_input(1);
+    let output = reactor
+        .create_compute(&[CellID::Input(input)], |v| {
+            calls.set(calls.get() + 1);
+            v[0] + calls.get()
+        })
+        .unwrap();
+    let cached = reactor.value_with_policy(CellID::Compute(output), CachePolicy::Always);
+    let fresh = reactor.value_with_policy(CellID::Compute(output), CachePolicy::Never);
+    assert_ne!(fresh, cached);
+    assert_eq!(
+        reactor.value_with_policy(CellID::Compute(output), CachePolicy::Always),
+        cached
+    );
+}
+
+#[test]
+fn cache_policy_session_updates_the_cache() {
+    let calls = std::cell::Cell::new(0);
+    let mut reactor = Reactor::new();
+    let input = reactor.create_input(1);
+    let output = reactor
+        .create_compute(&[CellID::Input(input)], |v| {
+            calls.set(calls.get() + 1);
+            v[0] + calls.get()
+        })
+        .unwrap();
+    let calls_before = calls.get();
+    let fresh = reactor.value_with_policy(CellID::Compute(output), CachePolicy::Session);
+    assert_eq!(calls.get(), calls_before + 1);
+    assert_eq!(
+        reactor.value_with_policy(CellID::Compute(output), CachePolicy::Always),
+        fresh
+    );
+}
+
+#[test]
+fn dynamic_cells_follow_the_cells_they_read() {
+    let calls = std::cell::Cell::new(0);
+    let mut reactor = Reactor::new();
+    let selector = reactor.create_input(0);
+    let x = reactor.create_input(10);
+    let y = reactor.create_input(20);
+    let counter = &calls;
+    let picked = reactor.create_dynamic(move |ctx| {
+        counter.set(counter.get() + 1);
+        if ctx.value(CellID::Input(selector)) == Some(0) {
+            ctx.value(CellID::Input(x)).unwrap()
+        } else {
+            ctx.value(CellID::Input(y)).unwrap()
+        }
+    });
+    let doubled = reactor
+        .create_compute(&[CellID::Compute(picked)], |v| v[0] * 2)
+        .unwrap();
+    assert_eq!(reactor.value(CellID::Compute(doubled)), Some(20));
+
+    let before = calls.get();
+    assert!(reactor.set_value(y, 21));
+    assert_eq!(calls.get(), before);
+
+    assert!(reactor.set_value(selector, 1));
+    assert_eq!(calls.get(), before + 1);
+    assert_eq!(reactor.value(CellID::Compute(doubled)), Some(42));
+
+    let before = calls.get();
+    assert!(reactor.set_value(x, 11));
+    assert_eq!(calls.get(), before);
+    assert!(reactor.set_value(y, 30));
+    assert_eq!(calls.get(), before + 1);
+    assert_eq!(reactor.value(CellID::Compute(doubled)), Some(60));
+}
+
+#[test]
+fn validate_all_reports_every_failing_cell() {
+    let armed = std::cell::Cell::new(false);
+    let mut reactor = Reactor::new();
+    let input = reactor.create_input(1);
+    let bad1 = reactor
+        .create_compute(&[CellID::Input(input)], |v| {
+            if armed.get() {
+                panic!("bad one");
+            }
+            v[0]
+        })
+        .unwrap();
+    let good = reactor
+        .create_compute(&[CellID::Input(input)], |v| v[0] + 1)
+        .unwrap();
+    let bad2 = reactor
+        .create_compute(&[CellID::Input(input)], |v| {
+            if armed.get() {
+                panic!("bad two: {}", v[0]);
+            }
+            v[0]
+        })
+        .unwrap();
+    let downstream = reactor
+        .create_compute(&[CellID::Compute(bad1), CellID::Compute(good)], |v| {
+            v[0] + v[1]
+        })
+        .unwrap();
+    assert!(reactor.validate_all().is_empty());
+
+    armed.set(true);
+    assert_eq!(
+        reactor.validate_all(),
+        vec![
+            (bad1, ComputeError::Panicked("bad one".to_string())),
+            (bad2, ComputeError::Panicked("bad two: 1".to_string())),
+            (downstream, ComputeError::FailedDependency(bad1)),
+        ]
+    );
+}
+
+#[test]
+fn add_callbacks_registers_a_batch() {
+    let cb1 = CallbackRecorder::new();
+    let cb2 = CallbackRecorder::new();
+    let cb3 = CallbackRecorder::new();
+    let mut reactor = Reactor::new();
+    let input = reactor.create_input(1);
+    let plus_one = reactor
+        .create_compute(&[CellID::Input(input)], |v| v[0] + 1)
+        .unwrap();
+    let times_two = reactor
+        .create_compute(&[CellID::Input(input)], |v| v[0] * 2)
+        .unwrap();
+    let mut dummy = Reactor::<i32>::new();
+    let dummy_input = dummy.create_input(1);
+    for _ in 0..3 {
+        dummy
+            .create_compute(&[CellID::Input(dummy_input)], |v| v[0])
+            .unwrap();
+    }
+    let missing = dummy
+        .create_compute(&[CellID::Input(dummy_input)], |v| v[0])
+        .unwrap();
+
+    let ids = reactor.add_callbacks(vec![
+        (plus_one, Box::new(|v| cb1.callback_called(v))),
+        (times_two, Box::new(|v| cb2.callback_called(v))),
+        (missing, Box::new(|_| panic!("never registered"))),
+        (plus_one, Box::new(|v| cb3.callback_called(v))),
+    ]);
+    assert_eq!(ids.len(), 4);
+    assert!(ids[0].is_some() && ids[1].is_some() && ids[3].is_some());
+    assert_eq!(ids[2], None);
+    assert_ne!(ids[0], ids[3]);
+
+    assert!(reactor.set_value(input, 5));
+    cb1.expect_to_have_been_called_with(6);
+    cb2.expect_to_have_been_called_with(10);
+    cb3.expect_to_have_been_called_with(6);
+}
+
+#[test]
+fn fn_override_applies_to_a_scratch_evaluation_only() {
+    let mut reactor = Reactor::new();
+    let a = reactor.create_input(3);
+    let b = reactor.create_input(4);
+    let sum = reactor
+        .create_compute(&[CellID::Input(a), CellID::Input(b)], |v| v[0] + v[1])
+        .unwrap();
+    let output = reactor
+        .create_compute(&[CellID::Compute(sum)], |v| v[0] * 10)
+        .unwrap();
+
+    assert_eq!(
+        reactor.value_with_fn_override(CellID::Compute(output), sum, |v| v[0] * v[1]),
+        Some(120)
+    );
+    assert_eq!(
+        reactor.value_with_fn_override(CellID::Compute(sum), sum, |v| v[0] * v[1]),
+        Some(12)
+    );
+    assert_eq!(reactor.value(CellID::Compute(output)), Some(70));
+    assert!(reactor.set_value(a, 5));
+    assert_eq!(reactor.value(CellID::Compute(output)), Some(90));
+}
+
+#[test]
+fn divergent_cells_compares_two_candidate_inputs() {
+    let mut reactor = Reactor::new();
+    let a = reactor.create_input(1i32);
+    let b = reactor.create_input(1);
+    let sign = reactor
+        .create_compute(&[CellID::Input(a)], |v| v[0].signum())
+        .unwrap();
+    let doubled = reactor
+        .create_compute(&[CellID::Input(a)], |v| v[0] * 2)
+        .unwrap();
+    let other = reactor
+        .create_compute(&[CellID::Input(b)], |v| v[0] + 1)
+        .unwrap();
+
+    assert_eq!(
+        reactor.divergent_cells(a, 5, 7),
+        vec![CellID::Input(a), CellID::Compute(doubled)]
+    );
+    assert_eq!(
+        reactor.divergent_cells(a, 5, -5),
+        vec![
+            CellID::Input(a),
+            CellID::Compute(sign),
+            CellID::Compute(doubled)
+        ]
+    );
+    assert!(reactor.divergent_cells(a, 3, 3).is_empty());
+    assert_eq!(reactor.value(CellID::Input(a)), Some(1));
+    assert_eq!(reactor.value(CellID::Compute(other)), Some(2));
+}
+
+#[test]
+fn callbacks_info_lists_callbacks_in_firing_order() {
+    let fired = std::cell::RefCell::new(Vec::new());
+    let mut reactor = Reactor::new();
+    let input = reactor.create_input(1);
+    let output = reactor
+        .create_compute(&[CellID::Input(input)], |v| v[0] + 1)
+        .unwrap();
+    let low = reactor
+        .add_callback_with_priority(output, -5, |_| fired.borrow_mut().push("low"))
+        .unwrap();
+    let plain = reactor
+        .add_callback(output, |_| fired.borrow_mut().push("plain"))
+        .unwrap();
+    let high = reactor
+        .add_callback_with_priority(output, 10, |_| fired.borrow_mut().push("high"))
+        .unwrap();
+    let muted = reactor
+        .add_callback_with_priority(output, 10, |_| fired.borrow_mut().push("muted"))
+        .unwrap();
+    assert!(reactor.set_callback_enabled(output, muted, false).is_ok());
+
+    assert_eq!(
+        reactor.callbacks_info(output),
+        Some(vec![
+            CallbackInfo {
+                id: high,
+                priority: 10,
+                enabled: true
+            },
+            CallbackInfo {
+                id: muted,
+                priority: 10,
+                enabled: false
+            },
+            CallbackInfo {
+                id: plain,
+                priority: 0,
+                enabled: true
+            },
+            CallbackInfo {
+                id: low,
+                priority: -5,
+                enabled: true
+            },
+        ])
+    );
+
+    assert!(reactor.set_value(input, 2));
+    assert_eq!(*fired.borrow(), vec!["high", "plain", "low"]);
+    assert!(reactor.remove_callback(output, low).is_ok());
+    assert_eq!(
+        reactor.set_callback_enabled(output, low, true),
+        Err(RemoveCallbackError::NonexistentCallback)
+    );
+    assert_eq!(Reactor::<i32>::new().callbacks_info(output), None);
+}
+
+#[test]
+fn full_snapshot_captures_every_cell_once() {
+    let shared_calls = std::cell::Cell::new(0);
+    let mut reactor = Reactor::new();
+    let a = reactor.create_input(2);
+    let b = reactor.create_input(3);
+    let shared = reactor
+        .create_compute(&[CellID::Input(a), CellID::Input(b)], |v| {
+            shared_calls.set(shared_calls.get() + 1);
+            v[0] * v[1]
+        })
+        .unwrap();
+    let plus = reactor
+        .create_compute(&[CellID::Compute(shared)], |v| v[0] + 1)
+        .unwrap();
+    let minus = reactor
+        .create_compute(&[CellID::Compute(shared)], |v| v[0] - 1)
+        .unwrap();
+
+    let before = shared_calls.get();
+    let snapshot = reactor.full_snapshot();
+    assert_eq!(shared_calls.get(), before + 1);
+    assert_eq!(snapshot.inputs, vec![2, 3]);
+    assert_eq!(snapshot.computes, vec![Some(6), Some(7), Some(5)]);
+    assert_eq!(snapshot.get(CellID::Input(b)), Some(3));
+    assert_eq!(snapshot.get(CellID::Compute(plus)), Some(7));
+    assert_eq!(snapshot.get(CellID::Compute(minus)), Some(5));
+}
+
+#[test]
+fn first_change_callbacks_fire_once_relative_to_creation() {
+    let cb = CallbackRecorder::new();
+    let late = CallbackRecorder::new();
+    let mut reactor = Reactor::new();
+    let input = reactor.create_input(1);
+    let output = reactor
+        .create_compute(&[CellID::Input(input)], |v| v[0] + 1)
+        .unwrap();
+    assert!(reactor
+        .add_first_change_callback(output, |v| cb.callback_called(v))
+        .is_some());
+
+    assert!(reactor.set_value(input, 2));
+    cb.expect_to_have_been_called_with(3);
+    assert!(reactor.set_value(input, 3));
+    cb.expect_not_to_have_been_called();
+
+    // the cell has changed since creation, so there is no first change left to observe
+    assert!(reactor
+        .add_first_change_callback(output, |v| late.callback_called(v))
+        .is_some());
+    assert!(reactor.set_value(input, 4));
+    late.expect_not_to_have_been_called();
+}
+
+#[test]
+fn sensitivities_measure_each_inputs_contribution() {
+    let mut reactor = Reactor::new();
+    let a = reactor.create_input(3.0f64);
+    let b = reactor.create_input(4.0);
+    let unrelated = reactor.create_input(5.0);
+    let out = reactor
+        .create_compute(&[CellID::Input(a), CellID::Input(b)], |v| 2.0 * v[0] + v[1])
+        .unwrap();
+    reactor
+        .create_compute(&[CellID::Input(unrelated)], |v| v[0])
+        .unwrap();
+
+    let sens = reactor.sensitivities(out, 0.5);
+    assert_eq!(sens.len(), 2);
+    assert_eq!(sens[0].0, a);
+    assert_eq!(sens[1].0, b);
+    assert!((sens[0].1 - 2.0 * sens[1].1).abs() < 1e-9);
+    assert!((sens[1].1 - 0.5).abs() < 1e-9);
+    assert_eq!(reactor.value(CellID::Compute(out)), Some(10.0));
+}
+
+#[test]
+fn all_paths_lists_both_sides_of_a_diamond() {
+    let mut reactor = Reactor::new();
+    let input = reactor.create_input(1);
+    let left = reactor
+        .create_compute(&[CellID::Input(input)], |v| v[0] + 1)
+        .unwrap();
+    let right = reactor
+        .create_compute(&[CellID::Input(input)], |v| v[0] - 1)
+        .unwrap();
+    let output = reactor
+        .create_compute(&[CellID::Compute(left), CellID::Compute(right)], |v| {
+            v[0] * v[1]
+        })
+        .unwrap();
+    assert_eq!(
+        reactor.all_paths(CellID::Input(input), CellID::Compute(output)),
+        vec![
+            vec![
+                CellID::Input(input),
+                CellID::Compute(left),
+                CellID::Compute(output)
+            ],
+            vec![
+                CellID::Input(input),
+                CellID::Compute(right),
+                CellID::Compute(output)
+            ],
+        ]
+    );
+    assert!(reactor
+        .all_paths(CellID::Compute(left), CellID::Compute(right))
+        .is_empty());
+    assert_eq!(
+        reactor.all_paths(CellID::Compute(left), CellID::Compute(left)),
+        vec![vec![CellID::Compute(left)]]
+    );
+}
+
+#[test]
+fn reactor_handle_swaps_the_whole_graph() {
+    let mut old = Reactor::new();
+    let old_input = old.create_input(1);
+    let old_output = old
+        .create_compute(&[CellID::Input(old_input)], |v| v[0] + 1)
+        .unwrap();
+
+    let handle = ReactorHandle::new(old);
+    let reader = handle.clone();
+    assert_eq!(reader.borrow().value(CellID::Compute(old_output)), Some(2));
+
+    let mut new = Reactor::new();
+    let a = new.create_input(10);
+    let b = new.create_input(20);
+    let sum = new
+        .create_compute(&[CellID::Input(a), CellID::Input(b)], |v| v[0] + v[1])
+        .unwrap();
+    let replaced = handle.swap(new);
+    assert_eq!(replaced.value(CellID::Compute(old_output)), Some(2));
+
+    assert_eq!(reader.borrow().value(CellID::Compute(sum)), Some(30));
+    assert!(handle.borrow_mut().set_value(b, 5));
+    assert_eq!(reader.borrow().value(CellID::Compute(sum)), Some(15));
+}
+
+#[test]
+fn value_at_generation_reads_past_values() {
+    let mut reactor = Reactor::new();
+    let input = reactor.create_input(1);
+    let output = reactor
+        .create_compute(&[CellID::Input(input)], |v| v[0] * 10)
+        .unwrap();
+    let start = reactor.current_generation();
+
+    assert!(reactor.set_value(input, 2));
+    let second = reactor.current_generation();
+    assert_eq!(second, start + 1);
+    assert!(reactor.set_value(input, 2));
+    assert_eq!(reactor.current_generation(), second);
+    assert!(reactor.set_value(input, 3));
+
+    assert_eq!(
+        reactor.value_at_generation(CellID::Compute(output), start),
+        Some(10)
+    );
+    assert_eq!(
+        reactor.value_at_generation(CellID::Compute(output), second),
+        Some(20)
+    );
+    assert_eq!(
+        reactor.value_at_generation(CellID::Input(input), second),
+        Some(2)
+    );
+    assert_eq!(
+        reactor.value_at_generation(CellID::Compute(output), reactor.current_generation()),
+        Some(30)
+    );
+    assert_eq!(
+        reactor.value_at_generation(CellID::Compute(output), reactor.current_generation() + 1),
+        None
+    );
+}
+
+#[test]
+fn value_at_generation_forgets_generations_outside_the_window() {
+    let mut reactor = Reactor::new();
+    let input = reactor.create_input(0);
+    let steady = reactor.create_input(7);
+    for i in 1..=(GENERATION_WINDOW as i32 + 5) {
+        assert!(reactor.set_value(input, i));
+    }
+    let now = reactor.current_generation();
+    assert_eq!(reactor.value_at_generation(CellID::Input(input), 0), None);
+    assert_eq!(
+        reactor.value_at_generation(CellID::Input(input), now - GENERATION_WINDOW),
+        Some(5)
+    );
+    assert_eq!(
+        reactor.value_at_generation(CellID::Input(steady), now - GENERATION_WINDOW),
+        Some(7)
+    );
+}
+
+#[test]
+fn subgraph_boundary_of_a_chain_partition() {
+    let mut reactor = Reactor::new();
+    let input = reactor.create_input(1);
+    let first = reactor
+        .create_compute(&[CellID::Input(input)], |v| v[0] + 1)
+        .unwrap();
+    let second = reactor
+        .create_compute(&[CellID::Compute(first)], |v| v[0] + 1)
+        .unwrap();
+    let third = reactor
+        .create_compute(&[CellID::Compute(second)], |v| v[0] + 1)
+        .unwrap();
+    let fourth = reactor
+        .create_compute(&[CellID::Compute(third)], |v| v[0] + 1)
+        .unwrap();
+
+    let middle = [
+        CellID::Compute(first),
+        CellID::Compute(second),
+        CellID::Compute(third),
+    ]
+    .iter()
+    .copied()
+    .collect();
+    assert_eq!(
+        reactor.subgraph_boundary(&middle),
+        (vec![CellID::Compute(first)], vec![CellID::Compute(third)])
+    );
+
+    let head = [CellID::Input(input), CellID::Compute(first)]
+        .iter()
+        .copied()
+        .collect();
+    assert_eq!(
+        reactor.subgraph_boundary(&head),
+        (vec![], vec![CellID::Compute(first)])
+    );
+    let tail = [CellID::Compute(fourth)].iter().copied().collect();
+    assert_eq!(
+        reactor.subgraph_boundary(&tail),
+        (vec![CellID::Compute(fourth)], vec![])
+    );
+}
+
+#[test]
+fn cells_can_be_looked_up_by_unique_key() {
+    let mut reactor = Reactor::new();
+    let price = reactor.create_input(10);
+    let units = reactor.create_input(3);
+    let revenue = reactor
+        .create_compute(&[CellID::Input(price), CellID::Input(units)], |v| {
+            v[0] * v[1]
+        })
+        .unwrap();
+
+    assert!(reactor.set_key(CellID::Input(price), "price").is_ok());
+    assert!(reactor.set_key(CellID::Compute(revenue), "revenue").is_ok());
+    assert_eq!(
+        reactor.cell_by_key("revenue"),
+        Some(CellID::Compute(revenue))
+    );
+    assert_eq!(reactor.cell_by_key("price"), Some(CellID::Input(price)));
+    assert_eq!(reactor.cell_by_key("units"), None);
+    assert_eq!(reactor.key(CellID::Compute(revenue)), Some("revenue"));
+
+    assert_eq!(
+        reactor.set_key(CellID::Input(units), "price"),
+        Err(KeyError::DuplicateKey(CellID::Input(price)))
+    );
+    assert!(reactor.set_key(CellID::Input(price), "unit_price").is_ok());
+    assert_eq!(reactor.cell_by_key("price"), None);
+    assert!(reactor.set_key(CellID::Input(units), "price").is_ok());
+    assert_eq!(reactor.cell_by_key("price"), Some(CellID::Input(units)));
+
+    assert_eq!(
+        Reactor::<i32>::new().set_key(CellID::Input(price), "price"),
+        Err(KeyError::NonexistentCell)
+    );
+}
+
+#[test]
+fn minimal_affecting_inputs_skips_the_unselected_branch() {
+    let mut reactor = Reactor::new();
+    let selector = reactor.create_input(1);
+    let x = reactor.create_input(10);
+    let y = reactor.create_input(20);
+    let select = reactor
+        .create_compute(
+            &[CellID::Input(selector), CellID::Input(x), CellID::Input(y)],
+            |v| if v[0] > 0 { v[1] } else { v[2] },
+        )
+        .unwrap();
+    let output = reactor
+        .create_compute(&[CellID::Compute(select)], |v| v[0] * 2)
+        .unwrap();
+    assert_eq!(reactor.minimal_affecting_inputs(output), vec![selector, x]);
+
+    assert!(reactor.set_value(selector, 0));
+    assert_eq!(reactor.minimal_affecting_inputs(output), vec![selector, y]);
+}
+
+#[test]
+fn minimal_affecting_inputs_skips_nudges_that_would_overflow() {
+    let mut reactor = Reactor::<u32>::new();
+    let low = reactor.create_input(0);
+    let high = reactor.create_input(u32::MAX);
+    let output = reactor
+        .create_compute(&[CellID::Input(low), CellID::Input(high)], |v| v[0] ^ v[1])
+        .unwrap();
+    assert_eq!(reactor.minimal_affecting_inputs(output), vec![low, high]);
+}
+
+#[test]
+fn callbacks_fire_in_topological_rank_order() {
+    let log = std::cell::RefCell::new(Vec::new());
+    let mut reactor = Reactor::new();
+    let input = reactor.create_input(1);
+    let parent = reactor
+        .create_compute(&[CellID::Input(input)], |v| v[0] + 1)
+        .unwrap();
+    let child = reactor
+        .create_compute(&[CellID::Compute(parent)], |v| v[0] * 2)
+        .unwrap();
+    // created last, but sits directly on the input
+    let sibling = reactor
+        .create_compute(&[CellID::Input(input)], |v| v[0] - 1)
+        .unwrap();
+    assert!(reactor
+        .add_callback(child, |_| log.borrow_mut().push("child"))
+        .is_some());
+    assert!(reactor
+        .add_callback(sibling, |_| log.borrow_mut().push("sibling"))
+        .is_some());
+    assert!(reactor
+        .add_callback(parent, |_| log.borrow_mut().push("parent"))
+        .is_some());
+
+    assert!(reactor.set_value(input, 2));
+    assert_eq!(*log.borrow(), vec!["parent", "sibling", "child"]);
+}
+
+#[test]
+fn value_thunks_evaluate_later_against_captured_inputs() {
+    let calls = std::cell::Cell::new(0);
+    let mut reactor = Reactor::new();
+    let a = reactor.create_input(2);
+    let b = reactor.create_input(3);
+    let product = reactor
+        .create_compute(&[CellID::Input(a), CellID::Input(b)], |v| {
+            calls.set(calls.get() + 1);
+            v[0] * v[1]
+        })
+        .unwrap();
+    let output = reactor
+        .create_compute(&[CellID::Compute(product)], |v| v[0] + 1)
+        .unwrap();
+
+    let thunk = reactor.value_thunk(CellID::Compute(output)).unwrap();
+    let input_thunk = reactor.value_thunk(CellID::Input(a)).unwrap();
+    assert!(reactor.set_value(a, 10));
+    assert_eq!(reactor.value(CellID::Compute(output)), Some(31));
+
+    let before = calls.get();
+    assert_eq!(thunk(), Some(7));
+    assert_eq!(calls.get(), before + 1);
+    assert_eq!(input_thunk(), Some(2));
+    assert!(Reactor::<i32>::new()
+        .value_thunk(CellID::Compute(output))
+        .is_none());
+}
+
+#[test]
+fn reset_input_restores_the_creation_value() {
+    let cb = CallbackRecorder::new();
+    let mut reactor = Reactor::new();
+    let a = reactor.create_input(1);
+    let b = reactor.create_input(2);
+    let sum = reactor
+        .create_compute(&[CellID::Input(a), CellID::Input(b)], |v| v[0] + v[1])
+        .unwrap();
+    assert!(reactor.set_value(a, 10));
+    assert!(reactor.set_value(b, 20));
+    assert!(reactor
+        .add_callback(sum, |v| cb.callback_called(v))
+        .is_some());
+
+    assert!(reactor.reset_input(a));
+    assert_eq!(reactor.value(CellID::Input(a)), Some(1));
+    assert_eq!(reactor.value(CellID::Input(b)), Some(20));
+    cb.expect_to_have_been_called_with(21);
+    assert!(!Reactor::<i32>::new().reset_input(a));
+}
+
+#[test]
+fn value_hash_tracks_value_equality() {
+    let mut reactor = Reactor::new();
+    let a = reactor.create_input(2);
+    let b = reactor.create_input(3);
+    let double = reactor
+        .create_compute(&[CellID::Input(a)], |v| v[0] * 2)
+        .unwrap();
+    let plus_one = reactor
+        .create_compute(&[CellID::Input(b)], |v| v[0] + 1)
+        .unwrap();
+    let before = reactor.value_hash(CellID::Compute(double)).unwrap();
+    assert_eq!(Some(before), reactor.value_hash(CellID::Compute(plus_one)));
+    assert_eq!(Some(before), reactor.value_hash(CellID::Compute(double)));
+
+    assert!(reactor.set_value(a, 5));
+    assert_ne!(Some(before), reactor.value_hash(CellID::Compute(double)));
+    assert_eq!(Reactor::<i32>::new().value_hash(CellID::Input(a)), None);
+}
+
+#[test]
+fn repeated_reads_do_not_recompute() {
+    let calls = std::cell::Cell::new(0);
+    let mut reactor = Reactor::new();
+    let input = reactor.create_input(1);
+    let mut last = CellID::Input(input);
+    for _ in 0..10 {
+        let counter = &calls;
+        last = CellID::Compute(
+            reactor
+                .create_compute(&[last], move |v| {
+                    counter.set(counter.get() + 1);
+                    v[0] + 1
+                })
+                .unwrap(),
+        );
+    }
+    let calls_before = calls.get();
+    for _ in 0..100 {
+        assert_eq!(reactor.value(last), Some(11));
+    }
+    assert_eq!(calls.get(), calls_before);
+
+    assert!(reactor.set_value(input, 2));
+    assert_eq!(calls.get(), calls_before + 10);
+    for _ in 0..100 {
+        assert_eq!(reactor.value(last), Some(12));
+    }
+    assert_eq!(calls.get(), calls_before + 10);
+}
+
+#[test]
+fn evaluate_subset_skips_unrequested_branches() {
+    let calls = std::cell::Cell::new(0);
+    let mut reactor = Reactor::new();
+    let input = reactor.create_input(1);
+    let counter = &calls;
+    let shared = reactor
+        .create_compute(&[CellID::Input(input)], move |v| {
+            counter.set(counter.get() + 1);
+            v[0] * 10
+        })
+        .unwrap();
+    let mut branches = Vec::new();
+    for offset in 0..50 {
+        let branch = reactor
+            .create_compute(&[CellID::Compute(shared)], move |v| {
+                counter.set(counter.get() + 1);
+                v[0] + offset
+            })
+            .unwrap();
+        branches.push(branch);
+    }
+
+    let calls_before = calls.get();
+    let values = reactor.evaluate_subset(&[branches[3], branches[7]]);
+    // the shared cell and the two requested branches, and nothing else
+    assert_eq!(calls.get(), calls_before + 3);
+    assert_eq!(values.len(), 2);
+    assert_eq!(values[&branches[3]], 13);
+    assert_eq!(values[&branches[7]], 17);
+}
+
+#[test]
+fn set_by_keys_applies_all_updates_in_one_propagation() {
+    let cb = CallbackRecorder::new();
+    let mut reactor = Reactor::new();
+    let width = reactor.create_input(2);
+    let height = reactor.create_input(3);
+    let area = reactor
+        .create_compute(&[CellID::Input(width), CellID::Input(height)], |v| {
+            v[0] * v[1]
+        })
+        .unwrap();
+    reactor.set_key(CellID::Input(width), "width").unwrap();
+    reactor.set_key(CellID::Input(height), "height").unwrap();
+    reactor.set_key(CellID::Compute(area), "area").unwrap();
+    assert!(reactor
+        .add_callback(area, |v| cb.callback_called(v))
+        .is_some());
+
+    let mut updates = std::collections::HashMap::new();
+    updates.insert("width".to_string(), 4);
+    updates.insert("height".to_string(), 5);
+    assert_eq!(reactor.set_by_keys(&updates), Ok(vec![area]));
+    cb.expect_to_have_been_called_with(20);
+
+    updates.insert("depth".to_string(), 6);
+    updates.insert("width".to_string(), 7);
+    assert_eq!(
+        reactor.set_by_keys(&updates),
+        Err(KeyError::UnknownKey("depth".to_string()))
+    );
+    assert_eq!(reactor.value(CellID::Input(width)), Some(4));
+
+    let mut updates = std::collections::HashMap::new();
+    updates.insert("area".to_string(), 1);
+    assert_eq!(
+        reactor.set_by_keys(&updates),
+        Err(KeyError::UnknownKey("area".to_string()))
+    );
+    cb.expect_not_to_have_been_called();
+}
+
+#[test]
+fn deep_chains_stay_readable_when_propagation_stops_early() {
+    let mut reactor = Reactor::new();
+    let input = reactor.create_input(1);
+    let clamped = reactor
+        .create_compute(&[CellID::Input(input)], |v| v[0].min(10))
+        .unwrap();
+    let mut last = CellID::Compute(clamped);
+    for _ in 0..2 * MAX_EVAL_DEPTH {
+        last = CellID::Compute(reactor.create_compute(&[last], |v| v[0] + 1).unwrap());
+    }
+    let expected = 10 + 2 * MAX_EVAL_DEPTH as i32;
+    assert!(reactor.set_value(input, 20));
+    assert_eq!(reactor.value(last), Some(expected));
+    assert!(reactor.set_value(input, 30));
+    assert_eq!(reactor.value(last), Some(expected));
+}
+
+#[test]
+fn propagation_steps_one_layer_at_a_time() {
+    let cb = CallbackRecorder::new();
+    let mut reactor = Reactor::new();
+    let input = reactor.create_input(1);
+    let plus_one = reactor
+        .create_compute(&[CellID::Input(input)], |v| v[0] + 1)
+        .unwrap();
+    let times_two = reactor
+        .create_compute(&[CellID::Input(input)], |v| v[0] * 2)
+        .unwrap();
+    let sum = reactor
+        .create_compute(
+            &[CellID::Compute(plus_one), CellID::Compute(times_two)],
+            |v| v[0] + v[1],
+        )
+        .unwrap();
+    let negated = reactor
+        .create_compute(&[CellID::Compute(sum)], |v| -v[0])
+        .unwrap();
+    assert!(reactor
+        .add_callback(negated, |v| cb.callback_called(v))
+        .is_some());
+
+    let mut propagation = reactor.begin_set(input, 3).unwrap();
+    assert_eq!(
+        propagation.step_layer(),
+        Some(vec![(plus_one, 4), (times_two, 6)])
+    );
+    assert_eq!(propagation.step_layer(), Some(vec![(sum, 10)]));
+    cb.expect_not_to_have_been_called();
+    assert_eq!(propagation.step_layer(), Some(vec![(negated, -10)]));
+    cb.expect_to_have_been_called_with(-10);
+    assert_eq!(propagation.step_layer(), None);
+
+    assert_eq!(reactor.value(CellID::Compute(negated)), Some(-10));
+    assert!(reactor.begin_set(input, 3).unwrap().step_layer().is_none());
+}
+
+#[test]
+fn global_callbacks_fire_once_a_propagation_is_done() {
+    let seen = std::cell::RefCell::new(Vec::new());
+    let mut reactor = Reactor::new();
+    let input = reactor.create_input(1);
+    let plus_one = reactor.map1(input, |x| x + 1).unwrap();
+    let doubled = reactor.map1(plus_one, |x| x * 2).unwrap();
+    {
+        let seen = &seen;
+        reactor.add_global_callback(move |changes| seen.borrow_mut().push(changes.to_vec()));
+    }
+
+    let mut propagation = reactor.begin_set(input, 3).unwrap();
+    assert_eq!(propagation.step_layer(), Some(vec![(plus_one, 4)]));
+    assert!(seen.borrow().is_empty());
+    assert_eq!(propagation.step_layer(), Some(vec![(doubled, 8)]));
+    assert_eq!(propagation.step_layer(), None);
+    assert_eq!(*seen.borrow(), vec![vec![(plus_one, 4), (doubled, 8)]]);
+}
+
+#[test]
+fn diamond_join_is_recomputed_once_per_set_value() {
+    let calls = std::cell::Cell::new(0);
+    let seen = std::cell::RefCell::new(Vec::new());
+    let mut reactor = Reactor::new();
+    let a = reactor.create_input(1);
+    let b = reactor
+        .create_compute(&[CellID::Input(a)], |v| v[0] + 1)
+        .unwrap();
+    let c = reactor
+        .create_compute(&[CellID::Input(a)], |v| v[0] * 2)
+        .unwrap();
+    let counter = &calls;
+    let record = &seen;
+    let d = reactor
+        .create_compute(&[CellID::Compute(b), CellID::Compute(c)], move |v| {
+            counter.set(counter.get() + 1);
+            record.borrow_mut().push((v[0], v[1]));
+            v[0] + v[1]
+        })
+        .unwrap();
+
+    for (i, value) in [2, 5, 9].iter().enumerate() {
+        let calls_before = calls.get();
+        assert!(reactor.set_value(a, *value));
+        assert_eq!(calls.get(), calls_before + 1);
+        // never evaluated with one side updated and the other stale
+        assert_eq!(seen.borrow()[i + 1], (value + 1, value * 2));
+    }
+    assert_eq!(reactor.value(CellID::Compute(d)), Some(28));
+}
+
+#[test]
+fn input_values_round_trip() {
+    let mut reactor = Reactor::new();
+    let a = reactor.create_input(1);
+    let b = reactor.create_input(2);
+    let sum = reactor
+        .create_compute(&[CellID::Input(a), CellID::Input(b)], |v| v[0] + v[1])
+        .unwrap();
+    let saved = reactor.input_values();
+    assert_eq!(saved, vec![1, 2]);
+
+    assert!(reactor.set_value(a, 10));
+    assert!(reactor.set_value(b, 20));
+    assert_eq!(reactor.set_input_values(&saved), Ok(vec![sum]));
+    assert_eq!(reactor.input_values(), saved);
+    assert_eq!(reactor.value(CellID::Compute(sum)), Some(3));
+
+    assert_eq!(
+        reactor.set_input_values(&[5]),
+        Err(LengthMismatch {
+            expected: 2,
+            actual: 1
+        })
+    );
+    assert_eq!(reactor.input_values(), saved);
+}
+
+#[test]
+fn second_layer_callbacks_fire_once_for_a_change_two_hops_upstream() {
+    let cb = CallbackRecorder::new();
+    let mut reactor = Reactor::new();
+    let input = reactor.create_input(1);
+    let c1 = reactor
+        .create_compute(&[CellID::Input(input)], |v| v[0] + 1)
+        .unwrap();
+    let c2 = reactor
+        .create_compute(&[CellID::Compute(c1)], |v| v[0] * 10)
+        .unwrap();
+    assert!(reactor
+        .add_callback(c2, |v| cb.callback_called(v))
+        .is_some());
+    assert!(reactor.set_value(input, 4));
+    cb.expect_to_have_been_called_with(50);
+    assert!(reactor.set_value(input, 6));
+    cb.expect_to_have_been_called_with(70);
+}
+
+#[test]
+fn string_values_flow_through_compute_cells() {
+    let seen = std::cell::RefCell::new(Vec::new());
+    let mut reactor = Reactor::new();
+    let greeting = reactor.create_input("hello".to_string());
+    let name = reactor.create_input("world".to_string());
+    let joined = reactor
+        .create_compute(&[CellID::Input(greeting), CellID::Input(name)], |v| {
+            format!("{}, {}", v[0], v[1])
+        })
+        .unwrap();
+    assert!(reactor
+        .add_callback(joined, |v| seen.borrow_mut().push(v))
+        .is_some());
+    assert_eq!(
+        reactor.value(CellID::Compute(joined)),
+        Some("hello, world".to_string())
+    );
+
+    assert!(reactor.set_value(name, "reactor".to_string()));
+    assert_eq!(
+        reactor.value(CellID::Compute(joined)),
+        Some("hello, reactor".to_string())
+    );
+    assert!(reactor.set_value(name, "reactor".to_string()));
+    assert_eq!(*seen.borrow(), vec!["hello, reactor".to_string()]);
+}
+
+/// A value that counts how many times it's been cloned.
+#[derive(Debug)]
+struct CloneCounter<'a> {
+    text: String,
+    clones: &'a std::cell::Cell<usize>,
+}
+
+impl<'a> Clone for CloneCounter<'a> {
+    fn clone(&self) -> Self {
+        self.clones.set(self.clones.get() + 1);
+        CloneCounter {
+            text: self.text.clone(),
+            clones: self.clones,
+        }
+    }
+}
+
+impl<'a> PartialEq for CloneCounter<'a> {
+    fn eq(&self, other: &Self) -> bool {
+        self.text == other.text
+    }
+}
+
+#[test]
+fn ref_callbacks_see_the_new_value_without_cloning_it() {
+    let clones = std::cell::Cell::new(0);
+    let lengths = std::cell::RefCell::new(Vec::new());
+    let mut reactor = Reactor::new();
+    let input = reactor.create_input(CloneCounter {
+        text: "abc".to_string(),
+        clones: &clones,
+    });
+    let doubled = reactor
+        .create_compute(&[CellID::Input(input)], |v| CloneCounter {
+            text: v[0].text.repeat(2),
+            clones: v[0].clones,
+        })
+        .unwrap();
+    assert!(reactor
+        .add_callback_ref(doubled, |v| lengths.borrow_mut().push(v.text.len()))
+        .is_some());
+
+    let next = CloneCounter {
+        text: "abcd".to_string(),
+        clones: &clones,
+    };
+    clones.set(0);
+    assert!(reactor.set_value(input, next.clone()));
+    // not counting the clone handed to set_value
+    let propagation_clones = clones.get() - 1;
+    assert_eq!(*lengths.borrow(), vec![8]);
+
+    // a by-value callback costs one clone more per firing
+    assert!(reactor.add_callback(doubled, |_| {}).is_some());
+    let mut third = next;
+    third.text.push('e');
+    clones.set(0);
+    assert!(reactor.set_value(input, third));
+    assert_eq!(clones.get(), propagation_clones + 1);
+    assert_eq!(*lengths.borrow(), vec![8, 10]);
+}
+
+#[test]
+fn cells_over_only_constants_are_effectively_constant() {
+    let mut reactor = Reactor::new();
+    let input = reactor.create_input(1);
+    let two = reactor.create_constant(2);
+    let three = reactor.create_constant(3);
+    let product = reactor
+        .create_compute(&[CellID::Compute(two), CellID::Compute(three)], |v| {
+            v[0] * v[1]
+        })
+        .unwrap();
+    let scaled = reactor
+        .create_compute(&[CellID::Compute(product), CellID::Input(input)], |v| {
+            v[0] * v[1]
+        })
+        .unwrap();
+    assert_eq!(reactor.value(CellID::Compute(product)), Some(6));
+
+    let constant = reactor.effectively_constant();
+    assert_eq!(constant, vec![two, three, product]);
+    assert!(!constant.contains(&scaled));
+}
+
+#[test]
+fn removing_a_leaf_compute_cell() {
+    let cb = CallbackRecorder::new();
+    let mut reactor = Reactor::new();
+    let input = reactor.create_input(1);
+    let leaf = reactor
+        .create_compute(&[CellID::Input(input)], |v| v[0] + 1)
+        .unwrap();
+    assert!(reactor
+        .add_callback(leaf, |v| cb.callback_called(v))
+        .is_some());
+    reactor.set_key(CellID::Compute(leaf), "leaf").unwrap();
+
+    assert_eq!(reactor.remove_compute(leaf), Ok(()));
+    assert_eq!(reactor.value(CellID::Compute(leaf)), None);
+    assert_eq!(reactor.cell_by_key("leaf"), None);
+    assert!(reactor.set_value(input, 2));
+    cb.expect_not_to_have_been_called();
+    assert_eq!(
+        reactor.remove_compute(leaf),
+        Err(RemoveCellError::NonexistentCell)
+    );
+    assert_eq!(
+        reactor.create_compute(&[CellID::Compute(leaf)], |v| v[0]),
+        Err(CreateComputeError::NonexistentDependency(CellID::Compute(
+            leaf
+        )))
+    );
+
+    // later IDs don't shift
+    let next = reactor
+        .create_compute(&[CellID::Input(input)], |v| v[0] * 10)
+        .unwrap();
+    assert_ne!(next, leaf);
+    assert_eq!(reactor.value(CellID::Compute(next)), Some(20));
+}
+
+#[test]
+fn cells_with_dependents_cannot_be_removed() {
+    let mut reactor = Reactor::new();
+    let input = reactor.create_input(1);
+    let first = reactor
+        .create_compute(&[CellID::Input(input)], |v| v[0] + 1)
+        .unwrap();
+    let second = reactor
+        .create_compute(&[CellID::Input(input), CellID::Compute(first)], |v| {
+            v[0] + v[1]
+        })
+        .unwrap();
+    assert_eq!(
+        reactor.remove_input(input),
+        Err(RemoveCellError::HasDependents(vec![
+            CellID::Compute(first),
+            CellID::Compute(second),
+        ]))
+    );
+    assert_eq!(
+        reactor.remove_compute(first),
+        Err(RemoveCellError::HasDependents(vec![CellID::Compute(
+            second
+        )]))
+    );
+    assert_eq!(reactor.value(CellID::Input(input)), Some(1));
+
+    assert_eq!(reactor.remove_compute(second), Ok(()));
+    assert_eq!(reactor.remove_compute(first), Ok(()));
+    assert_eq!(reactor.remove_input(input), Ok(()));
+    assert_eq!(reactor.value(CellID::Input(input)), None);
+    assert!(!reactor.set_value(input, 5));
+}
+
+#[test]
+fn cannot_remove_an_input_a_lazy_dynamic_cell_just_started_reading() {
+    let mut reactor = Reactor::new();
+    reactor.set_eager(false);
+    let selector = reactor.create_input(0);
+    let x = reactor.create_input(10);
+    let y = reactor.create_input(20);
+    let picked = reactor.create_dynamic(move |ctx| {
+        if ctx.value(CellID::Input(selector)) == Some(0) {
+            ctx.value(CellID::Input(x)).unwrap()
+        } else {
+            ctx.value(CellID::Input(y)).unwrap()
+        }
+    });
+    assert!(reactor.set_value(selector, 1));
+    assert_eq!(reactor.value(CellID::Compute(picked)), Some(20));
+
+    assert_eq!(
+        reactor.remove_input(y),
+        Err(RemoveCellError::HasDependents(vec![CellID::Compute(
+            picked
+        )]))
+    );
+    assert_eq!(
+        reactor.dependencies(picked),
+        Some(vec![CellID::Input(selector), CellID::Input(y)])
+    );
+}
+
+#[test]
+fn set_values_fires_callbacks_once_per_batch() {
+    let cb = CallbackRecorder::new();
+    let mut reactor = Reactor::new();
+    let x = reactor.create_input(1);
+    let y = reactor.create_input(2);
+    let sum = reactor
+        .create_compute(&[CellID::Input(x), CellID::Input(y)], |v| v[0] + v[1])
+        .unwrap();
+    assert!(reactor
+        .add_callback(sum, |v| cb.callback_called(v))
+        .is_some());
+
+    assert!(reactor.set_values(&[(x, 10), (y, 20)]));
+    cb.expect_to_have_been_called_with(30);
+
+    assert!(reactor.set_values(&[(x, 5), (x, 7)]));
+    cb.expect_to_have_been_called_with(27);
+    assert_eq!(reactor.value(CellID::Input(x)), Some(7));
+
+    let mut bigger = Reactor::new();
+    bigger.create_input(0);
+    bigger.create_input(0);
+    let missing = bigger.create_input(0);
+    assert!(!reactor.set_values(&[(x, 1), (missing, 2)]));
+    assert_eq!(reactor.value(CellID::Input(x)), Some(7));
+    cb.expect_not_to_have_been_called();
+}
+
+#[test]
+fn watched_compute_cells_fire_on_changes_but_not_creation() {
+    let cb = CallbackRecorder::new();
+    let mut reactor = Reactor::new();
+    let input = reactor.create_input(1);
+    let (output, _) = reactor
+        .create_compute_watched(
+            &[CellID::Input(input)],
+            |v| v[0] * 2,
+            |v| cb.callback_called(v),
+        )
+        .unwrap();
+    cb.expect_not_to_have_been_called();
+    assert_eq!(reactor.value(CellID::Compute(output)), Some(2));
+
+    assert!(reactor.set_value(input, 3));
+    cb.expect_to_have_been_called_with(6);
+
+    let mut bigger = Reactor::new();
+    bigger.create_input(0);
+    let missing = bigger.create_input(0);
+    assert_eq!(
+        reactor
+            .create_compute_watched(&[CellID::Input(missing)], |v| v[0], |_| {})
+            .err(),
+        Some(CreateComputeError::NonexistentDependency(CellID::Input(
+            missing
+        )))
+    );
+}
+
+#[test]
+fn changes_between_generations() {
+    let mut reactor = Reactor::new();
+    let a = reactor.create_input(1);
+    let b = reactor.create_input(10);
+    let double_a = reactor
+        .create_compute(&[CellID::Input(a)], |v| v[0] * 2)
+        .unwrap();
+    let double_b = reactor
+        .create_compute(&[CellID::Input(b)], |v| v[0] * 2)
+        .unwrap();
+    let start = reactor.current_generation();
+
+    assert!(reactor.set_value(a, 2));
+    let after_a = reactor.current_generation();
+    assert!(reactor.set_value(b, 20));
+    assert!(reactor.set_value(a, 1));
+    let end = reactor.current_generation();
+
+    assert_eq!(reactor.changes_between(start, after_a), vec![(double_a, 4)]);
+    assert_eq!(
+        reactor.changes_between(after_a, end),
+        vec![(double_a, 2), (double_b, 40)]
+    );
+    // double_a changed and changed back
+    assert_eq!(reactor.changes_between(start, end), vec![(double_b, 40)]);
+    assert_eq!(reactor.changes_between(end, start), vec![]);
+
+    for i in 0..GENERATION_WINDOW as i32 {
+        assert!(reactor.set_value(b, 100 + i));
+    }
+    assert_eq!(reactor.changes_between(start, end), vec![]);
+}
+
+#[test]
+fn reactor_snapshot_round_trip_reproduces_values() {
+    let mut reactor = Reactor::new();
+    let a = reactor.create_input(1);
+    let b = reactor.create_input(2);
+    let sum = reactor
+        .create_compute(&[CellID::Input(a), CellID::Input(b)], |v| v[0] + v[1])
+        .unwrap();
+    let unused = reactor
+        .create_compute(&[CellID::Input(a)], |v| v[0])
+        .unwrap();
+    let doubled = reactor
+        .create_compute(&[CellID::Compute(sum)], |v| v[0] * 2)
+        .unwrap();
+    assert_eq!(reactor.remove_compute(unused), Ok(()));
+    assert!(reactor.set_value(a, 5));
+    let snapshot = reactor.to_snapshot();
+    #[cfg(feature = "serde")]
+    let snapshot: ReactorSnapshot<i32> =
+        serde_json::from_str(&serde_json::to_string(&snapshot).unwrap()).unwrap();
+
+    // the functions, in the order their cells were created, skipping removed cells
+    let mut functions = vec![
+        Box::new(|v: &[i32]| v[0] + v[1]) as BoxedComputeFn<i32>,
+        Box::new(|v: &[i32]| v[0] * 2),
+    ]
+    .into_iter();
+    let restored = Reactor::from_snapshot(&snapshot, |_| functions.next().unwrap()).unwrap();
+    for id in [
+        CellID::Input(a),
+        CellID::Input(b),
+        CellID::Compute(sum),
+        CellID::Compute(unused),
+        CellID::Compute(doubled),
+    ]
+    .iter()
+    {
+        assert_eq!(restored.value(*id), reactor.value(*id));
+    }
+    assert_eq!(restored.value(CellID::Compute(doubled)), Some(14));
+    assert_eq!(restored.value(CellID::Compute(unused)), None);
+}
+
+#[test]
+fn to_dot_renders_cells_edges_and_values() {
+    let mut reactor = Reactor::new();
+    let a = reactor.create_input(1);
+    let b = reactor.create_input(2);
+    let sum = reactor
+        .create_compute(&[CellID::Input(a), CellID::Input(b)], |v| v[0] + v[1])
+        .unwrap();
+    reactor.set_key(CellID::Compute(sum), "sum").unwrap();
+    assert_eq!(
+        reactor.to_dot(),
+        "digraph reactor {\n\
+         \x20   i0 [shape=box, label=\"input 0 = 1\"];\n\
+         \x20   i1 [shape=box, label=\"input 1 = 2\"];\n\
+         \x20   c0 [shape=ellipse, label=\"sum = 3\"];\n\
+         \x20   i0 -> c0;\n\
+         \x20   i1 -> c0;\n\
+         }\n"
+    );
+
+    let mut strings = Reactor::new();
+    strings.create_input("say \"hi\"".to_string());
+    assert!(strings
+        .to_dot()
+        .contains(r#"label="input 0 = \"say \\\"hi\\\"\"""#));
+}
+
+#[test]
+fn join_reached_by_paths_of_different_lengths_is_recomputed_once() {
+    let calls = std::cell::Cell::new(0);
+    let mut reactor = Reactor::new();
+    let a = reactor.create_input(1);
+    let b = reactor.create_input(1);
+    let mut long = CellID::Input(a);
+    for _ in 0..3 {
+        long = CellID::Compute(
+            reactor
+                .create_compute(&[long, CellID::Input(b)], |v| v[0] + v[1])
+                .unwrap(),
+        );
+    }
+    let counter = &calls;
+    let join = reactor
+        .create_compute(&[CellID::Input(a), long, CellID::Input(b)], move |v| {
+            counter.set(counter.get() + 1);
+            v[0] * 100 + v[1] * 10 + v[2]
+        })
+        .unwrap();
+
+    let calls_before = calls.get();
+    assert!(reactor.set_value(a, 2));
+    assert_eq!(calls.get(), calls_before + 1);
+    assert!(reactor.set_values(&[(a, 3), (b, 2)]));
+    assert_eq!(calls.get(), calls_before + 2);
+    assert_eq!(reactor.value(CellID::Compute(join)), Some(300 + 90 + 2));
+}
+
+#[test]
+fn dependencies_keep_declaration_order() {
+    let mut reactor = Reactor::new();
+    let a = reactor.create_input(1);
+    let b = reactor.create_input(2);
+    let first = reactor
+        .create_compute(&[CellID::Input(b), CellID::Input(a)], |v| v[0] - v[1])
+        .unwrap();
+    let deps = [CellID::Input(a), CellID::Compute(first), CellID::Input(b)];
+    let second = reactor
+        .create_compute(&deps, |v| v[0] + v[1] + v[2])
+        .unwrap();
+    assert_eq!(
+        reactor.dependencies(first),
+        Some(vec![CellID::Input(b), CellID::Input(a)])
+    );
+    assert_eq!(reactor.dependencies(second), Some(deps.to_vec()));
+    assert_eq!(Reactor::<i32>::new().dependencies(first), None);
+}
+
+#[test]
+fn in_propagation_is_only_true_while_callbacks_fire() {
+    let seen = std::cell::RefCell::new(Vec::new());
+    let mut reactor = Reactor::new();
+    let input = reactor.create_input(1);
+    let output = reactor
+        .create_compute(&[CellID::Input(input)], |v| v[0] + 1)
+        .unwrap();
+    let seen_ref = &seen;
+    let probe = reactor.propagation_probe();
+    assert!(reactor
+        .add_callback(output, move |_| seen_ref
+            .borrow_mut()
+            .push(probe.in_propagation()))
+        .is_some());
+
+    assert!(!reactor.in_propagation());
+    assert!(reactor.set_value(input, 2));
+    assert_eq!(*seen.borrow(), vec![true]);
+    assert!(!reactor.in_propagation());
+    assert!(!reactor.propagation_probe().in_propagation());
+}
+
+#[test]
+fn dependents_lists_direct_clients_in_id_order() {
+    let mut reactor = Reactor::new();
+    let input = reactor.create_input(1);
+    let first = reactor
+        .create_compute(&[CellID::Input(input)], |v| v[0] + 1)
+        .unwrap();
+    let second = reactor
+        .create_compute(&[CellID::Input(input)], |v| v[0] * 2)
+        .unwrap();
+    let third = reactor
+        .create_compute(&[CellID::Compute(first), CellID::Input(input)], |v| {
+            v[0] + v[1]
+        })
+        .unwrap();
+    assert_eq!(
+        reactor.dependents(CellID::Input(input)),
+        Some(vec![first, second, third])
+    );
+    assert_eq!(
+        reactor.dependents(CellID::Compute(first)),
+        Some(vec![third])
+    );
+    assert_eq!(reactor.dependents(CellID::Compute(third)), Some(vec![]));
+    assert_eq!(Reactor::<i32>::new().dependents(CellID::Input(input)), None);
+}
+
+#[test]
+fn independent_chains_are_separate_components() {
+    let mut reactor = Reactor::new();
+    let a = reactor.create_input(1);
+    let b = reactor.create_input(2);
+    let a1 = reactor
+        .create_compute(&[CellID::Input(a)], |v| v[0] + 1)
+        .unwrap();
+    let b1 = reactor
+        .create_compute(&[CellID::Input(b)], |v| v[0] + 1)
+        .unwrap();
+    let a2 = reactor
+        .create_compute(&[CellID::Compute(a1)], |v| v[0] + 1)
+        .unwrap();
+    let b2 = reactor
+        .create_compute(&[CellID::Compute(b1)], |v| v[0] + 1)
+        .unwrap();
+    assert_eq!(
+        reactor.components(),
+        vec![
+            vec![CellID::Input(a), CellID::Compute(a1), CellID::Compute(a2)],
+            vec![CellID::Input(b), CellID::Compute(b1), CellID::Compute(b2)],
+        ]
+    );
+
+    // joining the two chains merges them
+    reactor
+        .create_compute(&[CellID::Compute(a2), CellID::Compute(b2)], |v| v[0] + v[1])
+        .unwrap();
+    assert_eq!(reactor.components().len(), 1);
+}
+
+#[test]
+fn explain_renders_the_formula_tree() {
+    let mut reactor = Reactor::new();
+    let price = reactor.create_input(100);
+    let rate = reactor.create_input(8);
+    let tax = reactor
+        .create_compute(&[CellID::Input(price), CellID::Input(rate)], |v| {
+            v[0] * v[1] / 100
+        })
+        .unwrap();
+    let total = reactor
+        .create_compute(&[CellID::Input(price), CellID::Compute(tax)], |v| {
+            v[0] + v[1]
+        })
+        .unwrap();
+    assert_eq!(reactor.explain(total), "c1(i0, c0(i0, i1))");
+
+    reactor.set_key(CellID::Input(price), "price").unwrap();
+    reactor.set_key(CellID::Input(rate), "rate").unwrap();
+    reactor.set_key(CellID::Compute(tax), "tax").unwrap();
+    reactor.set_key(CellID::Compute(total), "total").unwrap();
+    assert_eq!(reactor.explain(total), "total(price, tax(price, rate))");
+
+    let mut last = CellID::Input(price);
+    for _ in 0..=MAX_EXPLAIN_DEPTH {
+        last = CellID::Compute(reactor.create_compute(&[last], |v| v[0]).unwrap());
+    }
+    let deep = match last {
+        CellID::Compute(id) => reactor.explain(id),
+        CellID::Input(_) => unreachable!(),
+    };
+    assert!(deep.ends_with(&format!("(...){}", ")".repeat(MAX_EXPLAIN_DEPTH))));
+    assert!(!deep.contains("price"));
+}
+
+#[test]
+fn cell_ids_are_listed_in_creation_order() {
+    let mut reactor = Reactor::new();
+    let a = reactor.create_input(1);
+    let c0 = reactor
+        .create_compute(&[CellID::Input(a)], |v| v[0] + 1)
+        .unwrap();
+    let b = reactor.create_input(2);
+    let c1 = reactor
+        .create_compute(&[CellID::Input(b)], |v| v[0] + 1)
+        .unwrap();
+    let c2 = reactor
+        .create_compute(&[CellID::Compute(c0), CellID::Compute(c1)], |v| v[0] + v[1])
+        .unwrap();
+    assert_eq!(reactor.input_ids().collect::<Vec<_>>(), vec![a, b]);
+    assert_eq!(reactor.compute_ids().collect::<Vec<_>>(), vec![c0, c1, c2]);
+
+    assert_eq!(reactor.remove_compute(c2), Ok(()));
+    assert_eq!(reactor.remove_compute(c1), Ok(()));
+    assert_eq!(
+        reactor.cell_ids().collect::<Vec<_>>(),
+        vec![CellID::Input(a), CellID::Input(b), CellID::Compute(c0)]
+    );
+}
+
+#[test]
+fn propagation_order_is_the_same_on_every_run() {
+    fn trace() -> Vec<String> {
+        let log = std::cell::RefCell::new(Vec::new());
+        let mut reactor = Reactor::new();
+        let input = reactor.create_input(0);
+        let log_ref = &log;
+        let mut cells = Vec::new();
+        for i in 0..8 {
+            let cell = reactor
+                .create_compute(&[CellID::Input(input)], move |v| {
+                    log_ref.borrow_mut().push(format!("compute {}", i));
+                    v[0] + i
+                })
+                .unwrap();
+            assert!(reactor
+                .add_callback(cell, move |_| log_ref
+                    .borrow_mut()
+                    .push(format!("callback {}", i)))
+                .is_some());
+            cells.push(CellID::Compute(cell));
+        }
+        reactor
+            .create_compute(&cells, move |v| {
+                log_ref.borrow_mut().push("join".to_string());
+                v.iter().sum()
+            })
+            .unwrap();
+        log.borrow_mut().clear();
+        for value in 1..4 {
+            assert!(reactor.set_value(input, value));
+        }
+        drop(reactor);
+        log.into_inner()
+    }
+
+    // every reactor's hash sets are seeded differently
+    let first = trace();
+    let expected_run = (0..8)
+        .flat_map(|i| vec![format!("compute {}", i), format!("callback {}", i)])
+        .chain(std::iter::once("join".to_string()))
+        .collect::<Vec<_>>();
+    assert_eq!(first, [&expected_run[..]; 3].concat());
+    for _ in 0..20 {
+        assert_eq!(trace(), first);
+    }
+}
+
+#[test]
+fn peek_value_never_calls_the_compute_function() {
+    let calls = std::cell::Cell::new(0);
+    let mut reactor = Reactor::new();
+    let input = reactor.create_input(1);
+    let output = reactor
+        .create_compute(&[CellID::Input(input)], |v| {
+            calls.set(calls.get() + 1);
+            v[0] * 3
+        })
+        .unwrap();
+    let calls_before = calls.get();
+    assert_eq!(reactor.peek_value(CellID::Compute(output)), Some(3));
+    assert_eq!(reactor.peek_value(CellID::Compute(output)), Some(3));
+    assert_eq!(reactor.peek_value(CellID::Input(input)), Some(1));
+    assert_eq!(calls.get(), calls_before);
+
+    assert!(reactor.set_value(input, 2));
+    let calls_before = calls.get();
+    assert_eq!(reactor.peek_value(CellID::Compute(output)), Some(6));
+    assert_eq!(calls.get(), calls_before);
+    assert_eq!(
+        Reactor::<i32>::new().peek_value(CellID::Compute(output)),
+        None
+    );
+}
+
+#[test]
+fn value_traced_lists_subexpressions_in_evaluation_order() {
+    let mut reactor = Reactor::new();
+    let a = reactor.create_input(2);
+    let b = reactor.create_input(3);
+    let product = reactor
+        .create_compute(&[CellID::Input(a), CellID::Input(b)], |v| v[0] * v[1])
+        .unwrap();
+    let plus_a = reactor
+        .create_compute(&[CellID::Compute(product), CellID::Input(a)], |v| {
+            v[0] + v[1]
+        })
+        .unwrap();
+    assert_eq!(
+        reactor.value_traced(CellID::Compute(plus_a)),
+        Some((
+            8,
+            vec![
+                (CellID::Input(a), 2),
+                (CellID::Input(b), 3),
+                (CellID::Compute(product), 6),
+                (CellID::Compute(plus_a), 8),
+            ]
+        ))
+    );
+    assert_eq!(
+        reactor.value_traced(CellID::Input(b)),
+        Some((3, vec![(CellID::Input(b), 3)]))
+    );
+}
+
+#[test]
+fn input_callbacks_fire_only_on_change() {
+    let cb = CallbackRecorder::new();
+    let mut reactor = Reactor::new();
+    let input = reactor.create_input(1);
+    reactor
+        .add_input_callback(input, |v| cb.callback_called(v))
+        .unwrap();
+    assert!(reactor.set_value(input, 2));
+    cb.expect_to_have_been_called_with(2);
+    assert!(reactor.set_value(input, 2));
+    cb.expect_not_to_have_been_called();
+}
+
+#[test]
+fn clear_callbacks_removes_every_callback() {
+    let cb1 = CallbackRecorder::new();
+    let cb2 = CallbackRecorder::new();
+    let cb3 = CallbackRecorder::new();
+    let mut reactor = Reactor::new();
+    let input = reactor.create_input(1);
+    let output = reactor
+        .create_compute(&[CellID::Input(input)], |v| v[0] + 1)
+        .unwrap();
+    reactor
+        .add_callback(output, |v| cb1.callback_called(v))
+        .unwrap();
+    reactor
+        .add_callback(output, |v| cb2.callback_called(v))
+        .unwrap();
+    let last = reactor
+        .add_callback(output, |v| cb3.callback_called(v))
+        .unwrap();
+    assert_eq!(reactor.clear_callbacks(output), Ok(3));
+    assert!(reactor.set_value(input, 2));
+    cb1.expect_not_to_have_been_called();
+    cb2.expect_not_to_have_been_called();
+    cb3.expect_not_to_have_been_called();
+    assert_eq!(reactor.clear_callbacks(output), Ok(0));
+    let next = reactor.add_callback(output, |_| ()).unwrap();
+    assert_ne!(next, last);
+}
+
+#[test]
+fn sync_reactor_can_be_read_while_another_thread_sets_values() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+
+    fn assert_send_sync<S: Send + Sync>() {}
+    assert_send_sync::<SyncReactor<i32>>();
+
+    let calls = Arc::new(AtomicUsize::new(0));
+    let reactor = Arc::new(SyncReactor::new());
+    let input = reactor.create_input(0);
+    let double = reactor
+        .create_compute(&[CellID::Input(input)], |v| v[0] * 2)
+        .unwrap();
+    let counter = Arc::clone(&calls);
+    reactor
+        .add_callback(double, move |_| {
+            counter.fetch_add(1, Ordering::SeqCst);
+        })
+        .unwrap();
+
+    let readers = (0..4)
+        .map(|_| {
+            let reactor = Arc::clone(&reactor);
+            thread::spawn(move || {
+                let mut last = 0;
+                for _ in 0..1000 {
+                    let v = reactor.value(CellID::Compute(double)).unwrap();
+                    assert_eq!(v % 2, 0);
+                    assert!(v >= last);
+                    last = v;
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    let writer = {
+        let reactor = Arc::clone(&reactor);
+        thread::spawn(move || {
+            for i in 1..=100 {
+                assert!(reactor.set_value(input, i));
+            }
+        })
+    };
+    writer.join().unwrap();
+    for reader in readers {
+        reader.join().unwrap();
+    }
+
+    assert_eq!(reactor.value(CellID::Compute(double)), Some(200));
+    assert_eq!(calls.load(Ordering::SeqCst), 100);
+}
+
+#[test]
+fn async_callbacks_return_futures_to_await_after_propagation() {
+    use std::future::Future;
+    use std::sync::Arc;
+    use std::task::{Context, Poll, Wake};
+
+    struct NoopWaker;
+    impl Wake for NoopWaker {
+        fn wake(self: Arc<Self>) {}
+    }
+    fn block_on<F: Future>(f: F) -> F::Output {
+        let waker = Arc::new(NoopWaker).into();
+        let mut cx = Context::from_waker(&waker);
+        let mut f = Box::pin(f);
+        loop {
+            if let Poll::Ready(v) = f.as_mut().poll(&mut cx) {
+                return v;
+            }
+        }
+    }
+
+    let log = std::cell::RefCell::new(Vec::new());
+    let mut reactor = Reactor::new();
+    let input = reactor.create_input(1);
+    let plus_one = reactor
+        .create_compute(&[CellID::Input(input)], |v| v[0] + 1)
+        .unwrap();
+    let times_two = reactor
+        .create_compute(&[CellID::Input(input)], |v| v[0] * 2)
+        .unwrap();
+    for id in [plus_one, times_two].iter() {
+        let log = &log;
+        reactor
+            .add_async_callback(*id, move |v| async move { log.borrow_mut().push(v) })
+            .unwrap();
+    }
+
+    assert!(reactor.set_value(input, 1));
+    assert!(reactor.take_futures().is_empty());
+
+    assert!(reactor.set_value(input, 5));
+    let futures = reactor.take_futures();
+    assert_eq!(futures.len(), 2);
+    assert!(log.borrow().is_empty());
+    for f in futures {
+        block_on(f);
+    }
+    let mut logged = log.borrow().clone();
+    logged.sort_unstable();
+    assert_eq!(logged, vec![6, 10]);
+    assert!(reactor.take_futures().is_empty());
+}
+
+#[test]
+fn recompute_count_runs_each_cell_of_a_diamond_once() {
+    let mut reactor = Reactor::new();
+    let input = reactor.create_input(1);
+    let left = reactor
+        .create_compute(&[CellID::Input(input)], |v| v[0] + 1)
+        .unwrap();
+    let right = reactor
+        .create_compute(&[CellID::Input(input)], |v| v[0] * 2)
+        .unwrap();
+    let bottom = reactor
+        .create_compute(&[CellID::Compute(left), CellID::Compute(right)], |v| {
+            v[0] + v[1]
+        })
+        .unwrap();
+    assert_eq!(reactor.value(CellID::Compute(bottom)), Some(4));
+    reactor.reset_recompute_count();
+    assert_eq!(reactor.recompute_count(), 0);
+
+    assert!(reactor.set_value(input, 2));
+    // without ordering by rank, `bottom` would run once per changed dependency
+    assert_eq!(reactor.recompute_count(), 3);
+    assert_eq!(reactor.value(CellID::Compute(bottom)), Some(7));
+    assert_eq!(reactor.recompute_count(), 3);
+
+    assert!(reactor.set_value(input, 2));
+    assert_eq!(reactor.recompute_count(), 3);
+}
+
+#[test]
+fn callbacks_fire_in_ascending_id_order() {
+    let fired = std::cell::RefCell::new(Vec::new());
+    let mut reactor = Reactor::new();
+    let input = reactor.create_input(1);
+    let output = reactor
+        .create_compute(&[CellID::Input(input)], |v| v[0] + 1)
+        .unwrap();
+    let mut ids = Vec::new();
+    for _ in 0..8 {
+        let fired = &fired;
+        let slot = ids.len();
+        ids.push(
+            reactor
+                .add_callback(output, move |_| fired.borrow_mut().push(slot))
+                .unwrap(),
+        );
+    }
+    for v in 2..5 {
+        fired.borrow_mut().clear();
+        assert!(reactor.set_value(input, v));
+        assert_eq!(*fired.borrow(), (0..8).collect::<Vec<_>>());
+    }
+    let mut sorted = ids.clone();
+    sorted.sort();
+    assert_eq!(ids, sorted);
+}
+
+#[test]
+fn create_compute_errors_say_why_creation_failed() {
+    let mut dummy = Reactor::new();
+    let _ = dummy.create_input(0);
+    let missing = dummy.create_input(0);
+    let mut reactor = Reactor::new();
+    let input = reactor.create_input(1);
+    match reactor.create_compute(&[CellID::Input(input), CellID::Input(missing)], |v| v[0]) {
+        Err(CreateComputeError::NonexistentDependency(id)) => {
+            assert_eq!(id, CellID::Input(missing))
+        }
+        other => panic!("expected a nonexistent dependency, got {:?}", other),
+    }
+
+    let sync = SyncReactor::new();
+    let _ = sync.create_input(1);
+    assert_eq!(
+        sync.create_compute(&[CellID::Input(missing)], |v| v[0]),
+        Err(CreateComputeError::NonexistentDependency(CellID::Input(
+            missing
+        )))
+    );
+}
+
+#[test]
+fn snapshot_maps_every_cell_to_its_value() {
+    use std::collections::HashMap;
+
+    let mut reactor = Reactor::new();
+    let input = reactor.create_input(3);
+    let left = reactor
+        .create_compute(&[CellID::Input(input)], |v| v[0] + 1)
+        .unwrap();
+    let right = reactor
+        .create_compute(&[CellID::Input(input)], |v| v[0] * 2)
+        .unwrap();
+    let bottom = reactor
+        .create_compute(&[CellID::Compute(left), CellID::Compute(right)], |v| {
+            v[0] * v[1]
+        })
+        .unwrap();
+    assert!(reactor.set_value(input, 4));
+    reactor.reset_recompute_count();
+
+    let expected = [
+        (CellID::Input(input), 4),
+        (CellID::Compute(left), 5),
+        (CellID::Compute(right), 8),
+        (CellID::Compute(bottom), 40),
+    ]
+    .iter()
+    .copied()
+    .collect::<HashMap<_, _>>();
+    assert_eq!(reactor.snapshot(), expected);
+    assert_eq!(reactor.recompute_count(), 0);
+}
+
+#[test]
+fn lazy_set_value_defers_recomputation_until_read() {
+    let calls = std::cell::Cell::new(0);
+    let cb = CallbackRecorder::new();
+    let mut reactor = Reactor::new();
+    let input = reactor.create_input(1);
+    let counter = &calls;
+    let output = reactor
+        .create_compute(&[CellID::Input(input)], move |v| {
+            counter.set(counter.get() + 1);
+            v[0] * 10
+        })
+        .unwrap();
+    reactor
+        .add_callback(output, |v| cb.callback_called(v))
+        .unwrap();
+    assert_eq!(calls.get(), 1);
+
+    reactor.set_eager(false);
+    assert!(reactor.set_value(input, 2));
+    assert!(reactor.set_value(input, 3));
+    assert_eq!(calls.get(), 1);
+    cb.expect_not_to_have_been_called();
+
+    assert_eq!(reactor.value(CellID::Compute(output)), Some(30));
+    assert_eq!(calls.get(), 2);
+    cb.expect_to_have_been_called_with(30);
+    assert_eq!(reactor.value(CellID::Compute(output)), Some(30));
+    assert_eq!(calls.get(), 2);
+}
+
+#[test]
+fn switching_back_to_eager_catches_up_stale_cells() {
+    let cb = CallbackRecorder::new();
+    let mut reactor = Reactor::new();
+    let input = reactor.create_input(1);
+    let plus_one = reactor
+        .create_compute(&[CellID::Input(input)], |v| v[0] + 1)
+        .unwrap();
+    let doubled = reactor
+        .create_compute(&[CellID::Compute(plus_one)], |v| v[0] * 2)
+        .unwrap();
+    reactor
+        .add_callback(doubled, |v| cb.callback_called(v))
+        .unwrap();
+
+    reactor.set_eager(false);
+    assert!(reactor.set_value(input, 4));
+    cb.expect_not_to_have_been_called();
+    reactor.set_eager(true);
+    cb.expect_to_have_been_called_with(10);
+
+    assert!(reactor.set_value(input, 5));
+    cb.expect_to_have_been_called_with(12);
+    assert_eq!(reactor.value(CellID::Compute(doubled)), Some(12));
+}
+
+#[test]
+fn fallible_compute_errors_surface_downstream_and_recover() {
+    let cb = CallbackRecorder::new();
+    let mut reactor = Reactor::new();
+    let numerator = reactor.create_input(12);
+    let denominator = reactor.create_input(3);
+    let ratio = reactor
+        .create_fallible_compute(
+            &[CellID::Input(numerator), CellID::Input(denominator)],
+            |v| {
+                if v[1] == 0 {
+                    Err("division by zero")
+                } else {
+                    Ok(v[0] / v[1])
+                }
+            },
+        )
+        .unwrap();
+    let plus_one = reactor
+        .create_compute(&[CellID::Compute(ratio)], |v| v[0] + 1)
+        .unwrap();
+    reactor
+        .add_callback(plus_one, |v| cb.callback_called(v))
+        .unwrap();
+    assert_eq!(reactor.try_value(CellID::Compute(plus_one)), Some(Ok(5)));
+
+    assert!(reactor.set_value(denominator, 0));
+    cb.expect_not_to_have_been_called();
+    assert_eq!(reactor.value(CellID::Compute(ratio)), None);
+    assert_eq!(reactor.value(CellID::Compute(plus_one)), None);
+    assert_eq!(
+        reactor.try_value(CellID::Compute(ratio)),
+        Some(Err(ComputeError::Failed("division by zero".to_string())))
+    );
+    assert_eq!(
+        reactor.try_value(CellID::Compute(plus_one)),
+        Some(Err(ComputeError::FailedDependency(ratio)))
+    );
+    assert_eq!(
+        reactor.validate_all(),
+        vec![
+            (ratio, ComputeError::Failed("division by zero".to_string())),
+            (plus_one, ComputeError::FailedDependency(ratio)),
+        ]
+    );
+
+    assert!(reactor.set_value(denominator, 4));
+    cb.expect_to_have_been_called_with(4);
+    assert_eq!(reactor.try_value(CellID::Compute(plus_one)), Some(Ok(4)));
+}
+
+#[test]
+fn named_cells_can_be_looked_up_by_name() {
+    let mut reactor = Reactor::new();
+    let price = reactor.create_named_input("price", 10).unwrap();
+    let total = reactor
+        .create_named_compute("total", &[CellID::Input(price)], |v| v[0] * 2)
+        .unwrap();
+    assert_eq!(reactor.cell_by_name("price"), Some(CellID::Input(price)));
+    assert_eq!(reactor.cell_by_name("total"), Some(CellID::Compute(total)));
+    assert_eq!(reactor.key(CellID::Compute(total)), Some("total"));
+    assert_eq!(reactor.cell_by_name("tax"), None);
+}
+
+#[test]
+fn duplicate_cell_names_are_rejected() {
+    let mut reactor = Reactor::new();
+    let price = reactor.create_named_input("price", 10).unwrap();
+    assert_eq!(
+        reactor.create_named_input("price", 20),
+        Err(KeyError::DuplicateKey(CellID::Input(price)))
+    );
+    assert_eq!(
+        reactor.create_named_compute("price", &[CellID::Input(price)], |v| v[0]),
+        Err(CreateComputeError::DuplicateKey(CellID::Input(price)))
+    );
+    // nothing was created by the rejected calls
+    assert_eq!(reactor.cell_ids().count(), 1);
+}
+
+#[test]
+fn reset_restores_every_input_and_propagates_once() {
+    let sum_cb = CallbackRecorder::new();
+    let product_cb = CallbackRecorder::new();
+    let mut reactor = Reactor::new();
+    let a = reactor.create_input(1);
+    let b = reactor.create_input(2);
+    let c = reactor.create_input(3);
+    let sum = reactor
+        .create_compute(&[CellID::Input(a), CellID::Input(b)], |v| v[0] + v[1])
+        .unwrap();
+    let product = reactor
+        .create_compute(&[CellID::Input(c)], |v| v[0] * 10)
+        .unwrap();
+    let initial = reactor.snapshot();
+    reactor
+        .add_callback(sum, |v| sum_cb.callback_called(v))
+        .unwrap();
+    reactor
+        .add_callback(product, |v| product_cb.callback_called(v))
+        .unwrap();
+
+    assert!(reactor.set_values(&[(a, 5), (b, 6)]));
+    sum_cb.expect_to_have_been_called_with(11);
+
+    reactor.reset();
+    assert_eq!(reactor.snapshot(), initial);
+    sum_cb.expect_to_have_been_called_with(3);
+    product_cb.expect_not_to_have_been_called();
+}
+
+#[test]
+fn cell_generation_only_moves_on_real_changes() {
+    let mut reactor = Reactor::new();
+    let input = reactor.create_input(1);
+    let parity = reactor
+        .create_compute(&[CellID::Input(input)], |v| v[0] % 2)
+        .unwrap();
+    assert_eq!(reactor.generation(CellID::Input(input)), Some(0));
+    assert_eq!(reactor.generation(CellID::Compute(parity)), Some(0));
+
+    assert!(reactor.set_value(input, 2));
+    assert_eq!(reactor.generation(CellID::Input(input)), Some(1));
+    assert_eq!(reactor.generation(CellID::Compute(parity)), Some(1));
+
+    assert!(reactor.set_value(input, 2));
+    assert_eq!(reactor.generation(CellID::Input(input)), Some(1));
+
+    assert!(reactor.set_value(input, 4));
+    assert_eq!(reactor.generation(CellID::Input(input)), Some(2));
+    assert_eq!(reactor.generation(CellID::Compute(parity)), Some(1));
+}
+
+#[test]
+fn subscriptions_stop_firing_once_dropped_or_unsubscribed() {
+    let kept = CallbackRecorder::new();
+    let dropped = CallbackRecorder::new();
+    let unsubscribed = CallbackRecorder::new();
+    let mut reactor = Reactor::new();
+    let input = reactor.create_input(1);
+    let output = reactor
+        .create_compute(&[CellID::Input(input)], |v| v[0] + 1)
+        .unwrap();
+    let _keep = reactor
+        .subscribe(output, |v| kept.callback_called(v))
+        .unwrap();
+    let drop_me = reactor
+        .subscribe(output, |v| dropped.callback_called(v))
+        .unwrap();
+    let unsubscribe_me = reactor
+        .subscribe(output, |v| unsubscribed.callback_called(v))
+        .unwrap();
+    assert_eq!(unsubscribe_me.cell(), output);
+
+    assert!(reactor.set_value(input, 2));
+    kept.expect_to_have_been_called_with(3);
+    dropped.expect_to_have_been_called_with(3);
+    unsubscribed.expect_to_have_been_called_with(3);
+
+    drop(drop_me);
+    let id = unsubscribe_me.id();
+    unsubscribe_me.unsubscribe(&mut reactor);
+    assert_eq!(
+        reactor.remove_callback(output, id),
+        Err(RemoveCallbackError::NonexistentCallback)
+    );
+
+    assert!(reactor.set_value(input, 3));
+    kept.expect_to_have_been_called_with(4);
+    dropped.expect_not_to_have_been_called();
+    unsubscribed.expect_not_to_have_been_called();
+}
+
+#[test]
+fn once_callbacks_fire_for_the_next_change_only() {
+    let cb = CallbackRecorder::new();
+    let mut reactor = Reactor::new();
+    let input = reactor.create_input(1);
+    let output = reactor
+        .create_compute(&[CellID::Input(input)], |v| v[0] + 1)
+        .unwrap();
+    let id = reactor
+        .add_once_callback(output, |v| cb.callback_called(v))
+        .unwrap();
+    assert!(reactor.set_value(input, 2));
+    cb.expect_to_have_been_called_with(3);
+    assert!(reactor.set_value(input, 3));
+    cb.expect_not_to_have_been_called();
+    assert_eq!(
+        reactor.remove_callback(output, id),
+        Err(RemoveCallbackError::NonexistentCallback)
+    );
+}
+
+#[test]
+fn batches_fire_callbacks_once_for_net_changes_only() {
+    let flipped = CallbackRecorder::new();
+    let moved = CallbackRecorder::new();
+    let input_cb = CallbackRecorder::new();
+    let mut reactor = Reactor::new();
+    let a = reactor.create_input(1);
+    let b = reactor.create_input(10);
+    let plus_one = reactor
+        .create_compute(&[CellID::Input(a)], |v| v[0] + 1)
+        .unwrap();
+    let doubled = reactor
+        .create_compute(&[CellID::Input(b)], |v| v[0] * 2)
+        .unwrap();
+    reactor
+        .add_callback(plus_one, |v| flipped.callback_called(v))
+        .unwrap();
+    reactor
+        .add_callback(doubled, |v| moved.callback_called(v))
+        .unwrap();
+    reactor
+        .add_input_callback(a, |v| input_cb.callback_called(v))
+        .unwrap();
+
+    reactor.begin_batch();
+    assert!(reactor.set_value(a, 2));
+    assert_eq!(reactor.value(CellID::Compute(plus_one)), Some(3));
+    assert!(reactor.set_value(b, 11));
+    assert!(reactor.set_value(b, 12));
+    assert!(reactor.set_value(a, 1));
+    flipped.expect_not_to_have_been_called();
+    moved.expect_not_to_have_been_called();
+    reactor.end_batch();
+
+    flipped.expect_not_to_have_been_called();
+    input_cb.expect_not_to_have_been_called();
+    moved.expect_to_have_been_called_with(24);
+
+    reactor.batch(|r| {
+        assert!(r.set_value(a, 5));
+        assert!(r.set_value(a, 6));
+    });
+    input_cb.expect_to_have_been_called_with(6);
+    flipped.expect_to_have_been_called_with(7);
+}
+
+#[test]
+fn map_constructors_take_positional_arguments() {
+    let mut reactor = Reactor::new();
+    let a = reactor.create_input(2);
+    let b = reactor.create_input(3);
+    let c = reactor.create_input(4);
+    let doubled = reactor.map1(a, |x| x * 2).unwrap();
+    let diff = reactor.map2(b, doubled, |x, y| x - y).unwrap();
+    let mixed = reactor
+        .map3(a, b, c, |x, y, z| x * 100 + y * 10 + z)
+        .unwrap();
+    assert_eq!(reactor.value(CellID::Compute(doubled)), Some(4));
+    assert_eq!(reactor.value(CellID::Compute(diff)), Some(-1));
+    assert_eq!(reactor.value(CellID::Compute(mixed)), Some(234));
+
+    assert!(reactor.set_value(a, 5));
+    assert_eq!(reactor.value(CellID::Compute(diff)), Some(-7));
+    assert_eq!(reactor.value(CellID::Compute(mixed)), Some(534));
+}
+
+#[test]
+fn map_constructors_reject_nonexistent_dependencies() {
+    let mut dummy = Reactor::new();
+    let _ = dummy.create_input(0);
+    let missing = dummy.create_input(0);
+    let mut reactor = Reactor::new();
+    let input = reactor.create_input(1);
+    assert_eq!(
+        reactor.map2(input, missing, |x, y| x + y),
+        Err(CreateComputeError::NonexistentDependency(CellID::Input(
+            missing
+        )))
+    );
+}
+
+#[test]
+fn fold_cells_accumulate_across_updates() {
+    let cb = CallbackRecorder::new();
+    let mut reactor = Reactor::new();
+    let input = reactor.create_input(1);
+    let running_sum = reactor
+        .create_fold(&[CellID::Input(input)], 0, |sum, v| sum + v[0])
+        .unwrap();
+    let running_max = reactor
+        .create_fold(&[CellID::Input(input)], i32::MIN, |max, v| *max.max(&v[0]))
+        .unwrap();
+    reactor
+        .add_callback(running_sum, |v| cb.callback_called(v))
+        .unwrap();
+    assert_eq!(reactor.value(CellID::Compute(running_sum)), Some(1));
+
+    for (v, sum) in [(5, 6), (2, 8), (-3, 5)].iter() {
+        assert!(reactor.set_value(input, *v));
+        assert_eq!(reactor.value(CellID::Compute(running_sum)), Some(*sum));
+        cb.expect_to_have_been_called_with(*sum);
+    }
+    // setting the same value again doesn't reach the fold
+    assert!(reactor.set_value(input, -3));
+    assert_eq!(reactor.value(CellID::Compute(running_sum)), Some(5));
+    cb.expect_not_to_have_been_called();
+    assert_eq!(reactor.value(CellID::Compute(running_max)), Some(5));
+
+    // a scratch evaluation gives the current value, without taking another step
+    assert_eq!(
+        reactor.value_with_policy(CellID::Compute(running_sum), CachePolicy::Never),
+        Some(5)
+    );
+    assert_eq!(reactor.value(CellID::Compute(running_sum)), Some(5));
+}
+
+#[test]
+fn callbacks_can_read_sibling_cells() {
+    let seen = std::cell::RefCell::new(Vec::new());
+    let mut reactor = Reactor::new();
+    let input = reactor.create_input(1);
+    let plus_one = reactor
+        .create_compute(&[CellID::Input(input)], |v| v[0] + 1)
+        .unwrap();
+    let times_ten = reactor
+        .create_compute(&[CellID::Input(input)], |v| v[0] * 10)
+        .unwrap();
+    let sum = reactor
+        .create_compute(
+            &[CellID::Compute(plus_one), CellID::Compute(times_ten)],
+            |v| v[0] + v[1],
+        )
+        .unwrap();
+    reactor
+        .add_callback_with_reactor(plus_one, {
+            let seen = &seen;
+            move |v, r| {
+                seen.borrow_mut().push((
+                    v,
+                    r.value(CellID::Compute(times_ten)).unwrap(),
+                    r.value(CellID::Compute(sum)).unwrap(),
+                ))
+            }
+        })
+        .unwrap();
+
+    assert!(reactor.set_value(input, 2));
+    assert!(reactor.set_value(input, 3));
+    assert_eq!(*seen.borrow(), vec![(3, 20, 23), (4, 30, 34)]);
+    assert_eq!(reactor.value(CellID::Compute(sum)), Some(34));
+}
+
+#[test]
+fn debug_output_shows_cells_and_values() {
+    let mut reactor = Reactor::new();
+    let input = reactor.create_input(7);
+    let output = reactor
+        .create_compute(&[CellID::Input(input)], |v| v[0] * 6)
+        .unwrap();
+    reactor.add_callback(output, |_| ()).unwrap();
+    let debug = format!("{:?}", reactor);
+    assert!(debug.contains("InputCellID(0): 7"), "{}", debug);
+    assert!(debug.contains("id: ComputeCellID(0)"), "{}", debug);
+    assert!(debug.contains("deps: [Input(InputCellID(0))]"), "{}", debug);
+    assert!(debug.contains("callbacks: 1"), "{}", debug);
+    assert!(debug.contains("value: Some(42)"), "{}", debug);
+}
+
+#[test]
+fn topological_order_puts_dependencies_first() {
+    let mut reactor = Reactor::new();
+    let a = reactor.create_input(1);
+    let b = reactor.create_input(2);
+    let ab = reactor.map2(a, b, |x, y| x + y).unwrap();
+    let lone = reactor.map1(b, |x| x * 3).unwrap();
+    let ab2 = reactor.map1(ab, |x| x * 2).unwrap();
+    let join = reactor.map3(ab2, lone, ab, |x, y, z| x + y + z).unwrap();
+    let tail = reactor.map2(join, a, |x, y| x - y).unwrap();
+
+    let order = reactor.topological_order();
+    assert_eq!(order.len(), 5);
+    let position = |id: ComputeCellID| order.iter().position(|c| *c == id).unwrap();
+    for id in order.iter() {
+        for dep in reactor.dependencies(*id).unwrap() {
+            if let CellID::Compute(dep) = dep {
+                assert!(position(dep) < position(*id), "{:?} before {:?}", dep, id);
+            }
+        }
+    }
+    assert_eq!(position(tail), 4);
+}
+
+#[test]
+fn orphans_are_cells_without_dependents_or_callbacks() {
+    let mut reactor = Reactor::new();
+    let input = reactor.create_input(1);
+    let first = reactor.map1(input, |x| x + 1).unwrap();
+    let last = reactor.map1(first, |x| x * 2).unwrap();
+    let leaf = reactor.map1(input, |x| x - 1).unwrap();
+    let cbid = reactor.add_callback(last, |_| ()).unwrap();
+    assert_eq!(reactor.value(CellID::Compute(leaf)), Some(0));
+    assert_eq!(reactor.orphans(), vec![leaf]);
+
+    reactor.remove_callback(last, cbid).unwrap();
+    assert_eq!(reactor.orphans(), vec![last, leaf]);
+}
+
+#[test]
+fn replacing_a_compute_function_propagates_the_new_value() {
+    let sum_cb = CallbackRecorder::new();
+    let downstream_cb = CallbackRecorder::new();
+    let mut reactor = Reactor::new();
+    let a = reactor.create_input(2);
+    let b = reactor.create_input(2);
+    let combined = reactor.map2(a, b, |x, y| x + y).unwrap();
+    let plus_one = reactor.map1(combined, |x| x + 1).unwrap();
+    reactor
+        .add_callback(combined, |v| sum_cb.callback_called(v))
+        .unwrap();
+    reactor
+        .add_callback(plus_one, |v| downstream_cb.callback_called(v))
+        .unwrap();
+
+    // 2 * 2 == 2 + 2, so nothing changes
+    assert_eq!(reactor.set_compute_fn(combined, |v| v[0] * v[1]), Ok(()));
+    sum_cb.expect_not_to_have_been_called();
+    downstream_cb.expect_not_to_have_been_called();
+
+    assert!(reactor.set_value(b, 3));
+    sum_cb.expect_to_have_been_called_with(6);
+    downstream_cb.expect_to_have_been_called_with(7);
+
+    assert_eq!(reactor.set_compute_fn(combined, |v| v[0] - v[1]), Ok(()));
+    sum_cb.expect_to_have_been_called_with(-1);
+    downstream_cb.expect_to_have_been_called_with(0);
+    assert_eq!(reactor.value(CellID::Compute(plus_one)), Some(0));
+}
+
+#[test]
+fn rewiring_dependencies_recomputes_and_propagates() {
+    let cb = CallbackRecorder::new();
+    let mut reactor = Reactor::new();
+    let a = reactor.create_input(1);
+    let b = reactor.create_input(10);
+    let doubled = reactor.map1(a, |x| x * 2).unwrap();
+    let plus_one = reactor.map1(doubled, |x| x + 1).unwrap();
+    reactor
+        .add_callback(plus_one, |v| cb.callback_called(v))
+        .unwrap();
+
+    assert_eq!(
+        reactor.set_dependencies(doubled, &[CellID::Input(b)]),
+        Ok(())
+    );
+    assert_eq!(reactor.value(CellID::Compute(doubled)), Some(20));
+    cb.expect_to_have_been_called_with(21);
+    assert_eq!(reactor.dependents(CellID::Input(a)), Some(vec![]));
+
+    // the old input no longer matters, and the new one does
+    assert!(reactor.set_value(a, 5));
+    cb.expect_not_to_have_been_called();
+    assert!(reactor.set_value(b, 4));
+    cb.expect_to_have_been_called_with(9);
+}
+
+#[test]
+fn rewiring_to_a_later_cell_keeps_propagation_ordered() {
+    let mut reactor = Reactor::new();
+    let input = reactor.create_input(1);
+    let early = reactor.map1(input, |x| x).unwrap();
+    let late = reactor.map1(input, |x| x * 100).unwrap();
+    let join = reactor.map2(early, late, |x, y| x + y).unwrap();
+    assert_eq!(
+        reactor.set_dependencies(early, &[CellID::Compute(late)]),
+        Ok(())
+    );
+    assert_eq!(reactor.value(CellID::Compute(join)), Some(200));
+
+    let calls = std::cell::Cell::new(0);
+    let counter = &calls;
+    let mut reactor = reactor;
+    reactor
+        .add_callback(join, move |_| counter.set(counter.get() + 1))
+        .unwrap();
+    assert!(reactor.set_value(input, 2));
+    assert_eq!(reactor.value(CellID::Compute(join)), Some(400));
+    assert_eq!(calls.get(), 1);
+    assert!(reactor.validate_all().is_empty());
+    assert_eq!(reactor.topological_order(), vec![late, early, join]);
+}
+
+#[test]
+fn rewiring_rejects_nonexistent_dependencies_and_cycles() {
+    let mut dummy = Reactor::new();
+    let _ = dummy.create_input(0);
+    let missing = dummy.create_input(0);
+    let mut reactor = Reactor::new();
+    let input = reactor.create_input(1);
+    let first = reactor.map1(input, |x| x + 1).unwrap();
+    let second = reactor.map1(first, |x| x * 2).unwrap();
+
+    assert_eq!(
+        reactor.set_dependencies(first, &[CellID::Input(missing)]),
+        Err(CellID::Input(missing))
+    );
+    assert_eq!(
+        reactor.set_dependencies(first, &[CellID::Compute(second)]),
+        Err(CellID::Compute(second))
+    );
+    assert_eq!(
+        reactor.set_dependencies(first, &[CellID::Compute(first)]),
+        Err(CellID::Compute(first))
+    );
+    assert_eq!(
+        reactor.dependencies(first),
+        Some(vec![CellID::Input(input)])
+    );
+    assert!(reactor.set_value(input, 2));
+    assert_eq!(reactor.value(CellID::Compute(second)), Some(6));
+}
+
+#[test]
+fn snapshot_of_a_rewired_reactor_restores_dependencies_first() {
+    let mut reactor = Reactor::new();
+    let input = reactor.create_input(3);
+    let early = reactor.map1(input, |x| x + 1).unwrap();
+    let late = reactor.map1(input, |x| x * 10).unwrap();
+    assert_eq!(
+        reactor.set_dependencies(early, &[CellID::Compute(late)]),
+        Ok(())
+    );
+
+    let snapshot = reactor.to_snapshot();
+    let restored = Reactor::from_snapshot(&snapshot, |id| {
+        if id == early {
+            Box::new(|v: &[i32]| v[0] + 1) as BoxedComputeFn<i32>
+        } else {
+            Box::new(|v: &[i32]| v[0] * 10)
+        }
+    })
+    .unwrap();
+    assert_eq!(restored.value(CellID::Compute(early)), Some(31));
+    assert_eq!(restored.value(CellID::Compute(late)), Some(30));
+}
+
+#[test]
+fn snapshot_with_a_dependency_cycle_is_rejected() {
+    let mut reactor = Reactor::new();
+    let input = reactor.create_input(3);
+    reactor.map1(input, |x| x + 1).unwrap();
+    let first = reactor.map1(input, |x| x * 10).unwrap();
+    let second = reactor.map1(input, |x| x - 1).unwrap();
+
+    // the first cell leads into a cycle between the other two, which `set_dependencies` won't
+    // build
+    let mut snapshot = reactor.to_snapshot();
+    snapshot.computes[0] = vec![CellID::Compute(first)];
+    snapshot.computes[1] = vec![CellID::Compute(second)];
+    snapshot.computes[2] = vec![CellID::Compute(first)];
+    let restored = Reactor::from_snapshot(&snapshot, |_| {
+        Box::new(|v: &[i32]| v[0]) as BoxedComputeFn<i32>
+    });
+    assert_eq!(
+        restored.err(),
+        Some(CreateComputeError::Cycle(CellID::Compute(first)))
+    );
+}
+
+#[test]
+fn diamond_callbacks_never_see_a_glitch() {
+    let seen = std::cell::RefCell::new(Vec::new());
+    let mut reactor = Reactor::new();
+    let a = reactor.create_input(1);
+    let b = reactor.map1(a, |x| x + 1).unwrap();
+    let c = reactor.map1(a, |x| x * 10).unwrap();
+    // a deeper path on one side, so depth and creation order disagree
+    let c2 = reactor.map1(c, |x| x).unwrap();
+    let d = reactor.map2(b, c2, |x, y| x + y).unwrap();
+    {
+        let seen = &seen;
+        reactor
+            .add_callback(d, move |v| seen.borrow_mut().push(v))
+            .unwrap();
+    }
+    for x in 2..6 {
+        assert!(reactor.set_value(a, x));
+    }
+    // every value D reported is f(B, C) for a single value of A
+    let consistent = (2..6).map(|x| (x + 1) + x * 10).collect::<Vec<_>>();
+    assert_eq!(*seen.borrow(), consistent);
+}
+
+#[test]
+fn custom_equality_ignores_changes_within_tolerance() {
+    let cb = CallbackRecorder::new();
+    let mut reactor = Reactor::new();
+    let input = reactor.create_input(1.0);
+    let output = reactor
+        .create_compute_with_eq(
+            &[CellID::Input(input)],
+            |v| v[0] * 2.0,
+            |a: &f64, b: &f64| (a - b).abs() < 1e-9,
+        )
+        .unwrap();
+    let plus_one = reactor.map1(output, |x| x + 1.0).unwrap();
+    assert!(reactor
+        .add_callback(output, |v| cb.callback_called(v as i32))
+        .is_some());
+
+    assert!(reactor.set_value(input, 1.0 + 1e-12));
+    cb.expect_not_to_have_been_called();
+    assert_eq!(reactor.value(CellID::Compute(output)), Some(2.0));
+    assert_eq!(reactor.value(CellID::Compute(plus_one)), Some(3.0));
+
+    assert!(reactor.set_value(input, 2.0));
+    cb.expect_to_have_been_called_with(4);
+    assert_eq!(reactor.value(CellID::Compute(plus_one)), Some(5.0));
+}
+
+#[test]
+fn drain_changes_reports_each_changed_cell_once() {
+    let mut reactor = Reactor::new();
+    let a = reactor.create_input(1);
+    let b = reactor.create_input(10);
+    let sum = reactor.map2(a, b, |x, y| x + y).unwrap();
+    let parity = reactor.map1(a, |x| x % 2).unwrap();
+    assert_eq!(reactor.drain_changes(), vec![]);
+
+    assert!(reactor.set_value(a, 3));
+    assert!(reactor.set_value(a, 5));
+    assert!(reactor.set_value(b, 20));
+    assert_eq!(
+        reactor.drain_changes(),
+        vec![
+            (CellID::Input(a), 5),
+            (CellID::Input(b), 20),
+            (CellID::Compute(sum), 25),
+        ]
+    );
+    assert_eq!(reactor.drain_changes(), vec![]);
+
+    assert!(reactor.set_value(a, 6));
+    assert_eq!(
+        reactor.drain_changes(),
+        vec![
+            (CellID::Input(a), 6),
+            (CellID::Compute(sum), 26),
+            (CellID::Compute(parity), 0),
+        ]
+    );
+}
+
+#[cfg(feature = "petgraph")]
+#[test]
+fn petgraph_graph_has_a_node_per_cell_and_an_edge_per_dependency() {
+    let mut reactor = Reactor::new();
+    let a = reactor.create_input(1);
+    let b = reactor.create_input(2);
+    let _lonely = reactor.create_input(3);
+    let sum = reactor.map2(a, b, |x, y| x + y).unwrap();
+    let doubled = reactor.map1(sum, |x| x * 2).unwrap();
+    let gone = reactor.map1(a, |x| x).unwrap();
+    assert_eq!(reactor.remove_compute(gone), Ok(()));
+
+    let graph = reactor.to_petgraph();
+    assert_eq!(graph.node_count(), 5);
+    assert_eq!(graph.edge_count(), 3);
+    let mut edges = graph
+        .raw_edges()
+        .iter()
+        .map(|e| (graph[e.source()], graph[e.target()]))
+        .collect::<Vec<_>>();
+    edges.sort();
+    assert_eq!(
+        edges,
+        vec![
+            (CellID::Input(a), CellID::Compute(sum)),
+            (CellID::Input(b), CellID::Compute(sum)),
+            (CellID::Compute(sum), CellID::Compute(doubled)),
+        ]
+    );
+    assert!(petgraph::algo::toposort(&graph, None).is_ok());
+}
+
+#[test]
+fn json_values_are_keyed_by_cell() {
+    let mut reactor = Reactor::new();
+    let a = reactor.create_input(2);
+    let b = reactor.create_input(-3);
+    let _product = reactor.map2(a, b, |x, y| x * y).unwrap();
+    assert_eq!(
+        reactor.to_json_values(),
+        r#"{"input:0":2,"input:1":-3,"compute:0":-6}"#
+    );
+
+    let mut words = Reactor::new();
+    let word = words.create_input("say \"hi\"".to_string());
+    let _loud = words.map1(word, |w| w.to_uppercase()).unwrap();
+    let json = words.to_json_values();
+    assert_eq!(json, r#"{"input:0":"say \"hi\"","compute:0":"SAY \"HI\""}"#);
+    let parsed: std::collections::HashMap<String, String> = serde_json::from_str(&json).unwrap();
+    assert_eq!(parsed["compute:0"], "SAY \"HI\"");
+}
+
+#[test]
+fn json_values_quote_text_that_only_looks_numeric() {
+    let mut reactor = Reactor::new();
+    for s in [
+        "007", "5.", ".5", "-", "1e", "+1", "0", "-0.5", "1.5e-3", "2E+10",
+    ]
+    .iter()
+    {
+        reactor.create_input(s.to_string());
+    }
+    let json = reactor.to_json_values();
+    assert_eq!(
+        json,
+        concat!(
+            r#"{"input:0":"007","input:1":"5.","input:2":".5","input:3":"-","input:4":"1e","#,
+            r#""input:5":"+1","input:6":0,"input:7":-0.5,"input:8":1.5e-3,"input:9":2E+10}"#
+        )
+    );
+    assert!(serde_json::from_str::<serde_json::Value>(&json).is_ok());
+}
+
+#[test]
+fn callback_count_and_has_callback_track_registrations() {
+    let mut dummy = Reactor::new();
+    let _ = dummy.create_compute(&[], |_| 0).unwrap();
+    let missing = dummy.create_compute(&[], |_| 0).unwrap();
+    let mut reactor = Reactor::new();
+    let input = reactor.create_input(1);
+    let output = reactor.map1(input, |x| x + 1).unwrap();
+    assert_eq!(reactor.callback_count(output), Some(0));
+    assert_eq!(reactor.callback_count(missing), None);
+
+    let first = reactor.add_callback(output, |_| ()).unwrap();
+    let second = reactor.add_callback(output, |_| ()).unwrap();
+    assert_eq!(reactor.callback_count(output), Some(2));
+    assert!(reactor.has_callback(output, first));
+    assert!(!reactor.has_callback(missing, first));
+
+    assert_eq!(reactor.remove_callback(output, first), Ok(()));
+    assert_eq!(reactor.callback_count(output), Some(1));
+    assert!(!reactor.has_callback(output, first));
+    assert!(reactor.has_callback(output, second));
+}
+
+#[test]
+fn depth_counts_the_longest_path_from_an_input() {
+    let mut reactor = Reactor::new();
+    let input = reactor.create_input(1);
+    let one = reactor.map1(input, |x| x + 1).unwrap();
+    let two = reactor.map1(one, |x| x + 1).unwrap();
+    let three = reactor.map1(two, |x| x + 1).unwrap();
+    let depths = [
+        CellID::Input(input),
+        CellID::Compute(one),
+        CellID::Compute(two),
+        CellID::Compute(three),
+    ]
+    .iter()
+    .map(|id| reactor.depth(*id))
+    .collect::<Vec<_>>();
+    assert_eq!(depths, vec![Some(0), Some(1), Some(2), Some(3)]);
+
+    // a diamond whose sides differ in length takes the longer one
+    let join = reactor.map2(one, three, |x, y| x + y).unwrap();
+    assert_eq!(reactor.depth(CellID::Compute(join)), Some(4));
+
+    let mut dummy = Reactor::new();
+    let _ = dummy.create_input(0);
+    let _ = dummy.create_input(0);
+    let missing = dummy.create_input(0);
+    assert_eq!(reactor.depth(CellID::Input(missing)), None);
+}
+
+#[test]
+fn controlled_callback_removes_itself_when_asked() {
+    let seen = std::cell::RefCell::new(Vec::new());
+    let mut reactor = Reactor::new();
+    let input = reactor.create_input(1);
+    let output = reactor.map1(input, |x| x + 1).unwrap();
+    let cbid = {
+        let seen = &seen;
+        reactor
+            .add_controlled_callback(output, move |v| {
+                seen.borrow_mut().push(v);
+                if seen.borrow().len() < 3 {
+                    CallbackControl::Keep
+                } else {
+                    CallbackControl::Remove
+                }
+            })
+            .unwrap()
+    };
+    for x in 2..7 {
+        assert!(reactor.set_value(input, x));
+    }
+    assert_eq!(*seen.borrow(), vec![3, 4, 5]);
+    assert!(!reactor.has_callback(output, cbid));
+    assert_eq!(
+        reactor.remove_callback(output, cbid),
+        Err(RemoveCallbackError::NonexistentCallback)
+    );
+}
+
+#[test]
+fn restoring_a_checkpoint_brings_back_input_values() {
+    let cb = CallbackRecorder::new();
+    let mut reactor = Reactor::new();
+    let a = reactor.create_input(1);
+    let b = reactor.create_input(2);
+    let sum = reactor.map2(a, b, |x, y| x + y).unwrap();
+    reactor
+        .add_callback(sum, |v| cb.callback_called(v))
+        .unwrap();
+    let checkpoint = reactor.checkpoint();
+    assert_eq!(checkpoint.get(a), Some(1));
+    assert_eq!(checkpoint.get(b), Some(2));
+
+    assert!(reactor.set_value(a, 10));
+    cb.expect_to_have_been_called_with(12);
+    assert!(reactor.set_value(b, 20));
+    cb.expect_to_have_been_called_with(30);
+
+    assert_eq!(reactor.restore(&checkpoint), Ok(vec![sum]));
+    cb.expect_to_have_been_called_with(3);
+    assert_eq!(reactor.value(CellID::Input(a)), Some(1));
+    assert_eq!(reactor.value(CellID::Input(b)), Some(2));
+
+    // restoring the state the reactor is already in changes nothing
+    assert_eq!(reactor.restore(&checkpoint), Ok(vec![]));
+    cb.expect_not_to_have_been_called();
+}
+
+#[test]
+fn restoring_a_mismatched_checkpoint_fails() {
+    let mut reactor = Reactor::new();
+    let a = reactor.create_input(1);
+    let checkpoint = reactor.checkpoint();
+    let _b = reactor.create_input(2);
+    assert!(reactor.set_value(a, 5));
+    assert_eq!(
+        reactor.restore(&checkpoint),
+        Err(LengthMismatch {
+            expected: 2,
+            actual: 1
+        })
+    );
+    assert_eq!(reactor.value(CellID::Input(a)), Some(5));
+}
+
+#[test]
+fn checkpoint_diff_lists_changed_inputs_in_order() {
+    let mut reactor = Reactor::new();
+    let a = reactor.create_input(1);
+    let b = reactor.create_input(2);
+    let c = reactor.create_input(3);
+    let before = reactor.checkpoint();
+    assert!(reactor.set_value(c, 30));
+    assert!(reactor.set_value(a, 10));
+    assert!(reactor.set_value(b, 20));
+    assert!(reactor.set_value(b, 2));
+    let after = reactor.checkpoint();
+
+    assert_eq!(before.diff(&after), Ok(vec![(a, 1, 10), (c, 3, 30)]));
+    assert_eq!(after.diff(&before), Ok(vec![(a, 10, 1), (c, 30, 3)]));
+    assert_eq!(before.diff(&before), Ok(vec![]));
+
+    let _d = reactor.create_input(4);
+    assert_eq!(
+        before.diff(&reactor.checkpoint()),
+        Err(LengthMismatch {
+            expected: 3,
+            actual: 4
+        })
+    );
+}
+
+#[test]
+fn depends_on_follows_dependencies_transitively() {
+    let mut reactor = Reactor::new();
+    let input = reactor.create_input(1);
+    let other = reactor.create_input(2);
+    let one = reactor.map1(input, |x| x + 1).unwrap();
+    let two = reactor.map1(one, |x| x + 1).unwrap();
+    let side = reactor.map1(input, |x| x * 2).unwrap();
+    let join = reactor.map2(two, side, |x, y| x + y).unwrap();
+    let (input, other) = (CellID::Input(input), CellID::Input(other));
+    let (one, two, side, join) = (
+        CellID::Compute(one),
+        CellID::Compute(two),
+        CellID::Compute(side),
+        CellID::Compute(join),
+    );
+
+    // along the chain
+    assert!(reactor.depends_on(two, input));
+    assert!(reactor.depends_on(two, one));
+    assert!(!reactor.depends_on(one, two));
+    assert!(!reactor.depends_on(input, one));
+    // across the diamond
+    assert!(reactor.depends_on(join, one));
+    assert!(reactor.depends_on(join, side));
+    assert!(!reactor.depends_on(side, two));
+    assert!(!reactor.depends_on(join, other));
+    // every cell depends on itself
+    assert!(reactor.depends_on(input, input));
+    assert!(reactor.depends_on(join, join));
+}
+
+#[test]
+fn merged_reactors_keep_values_and_propagate() {
+    let cb = CallbackRecorder::new();
+    let mut first = Reactor::new();
+    let a = first.create_input(1);
+    let doubled = first.map1(a, |x| x * 2).unwrap();
+
+    let mut second = Reactor::new();
+    let b = second.create_input(10);
+    let c = second.create_input(20);
+    let sum = second.map2(b, c, |x, y| x + y).unwrap();
+    let plus_one = second.map1(sum, |x| x + 1).unwrap();
+    second
+        .add_callback(plus_one, |v| cb.callback_called(v))
+        .unwrap();
+    second.set_key(CellID::Input(b), "b").unwrap();
+
+    let remap = first.merge(second);
+    let (b, c) = (remap.input(b), remap.input(c));
+    let (sum, plus_one) = (remap.compute(sum), remap.compute(plus_one));
+    assert_eq!(first.value(CellID::Compute(sum)), Some(30));
+    assert_eq!(first.value(CellID::Compute(plus_one)), Some(31));
+    assert_eq!(first.value(CellID::Compute(doubled)), Some(2));
+    assert_eq!(first.cell_by_key("b"), Some(CellID::Input(b)));
+    assert_eq!(
+        first.dependencies(sum),
+        Some(vec![CellID::Input(b), CellID::Input(c)])
+    );
+    cb.expect_not_to_have_been_called();
+
+    // the merged cells propagate, and fire their callbacks, in their new home
+    assert!(first.set_value(b, 15));
+    cb.expect_to_have_been_called_with(36);
+    assert!(first.set_value(a, 4));
+    cb.expect_not_to_have_been_called();
+    assert_eq!(first.value(CellID::Compute(doubled)), Some(8));
+    assert!(first.validate_all().is_empty());
+}
+
+#[test]
+fn cell_counts_skip_removed_cells() {
+    let mut reactor = Reactor::<i32>::new();
+    assert!(reactor.is_empty());
+    assert_eq!(reactor.len(), 0);
+
+    let a = reactor.create_input(1);
+    let b = reactor.create_input(2);
+    let sum = reactor.map2(a, b, |x, y| x + y).unwrap();
+    let _doubled = reactor.map1(a, |x| x * 2).unwrap();
+    assert_eq!(reactor.num_inputs(), 2);
+    assert_eq!(reactor.num_computes(), 2);
+    assert_eq!(reactor.len(), 4);
+    assert!(!reactor.is_empty());
+
+    assert_eq!(reactor.remove_compute(sum), Ok(()));
+    assert_eq!(reactor.remove_input(b), Ok(()));
+    assert_eq!(reactor.num_inputs(), 1);
+    assert_eq!(reactor.num_computes(), 1);
+    assert_eq!(reactor.len(), 2);
+}
+
+#[test]
+fn global_callback_fires_once_with_every_changed_cell() {
+    let seen = std::cell::RefCell::new(Vec::new());
+    let mut reactor = Reactor::new();
+    let input = reactor.create_input(1);
+    let other = reactor.create_input(5);
+    let doubled = reactor.map1(input, |x| x * 2).unwrap();
+    let tripled = reactor.map1(input, |x| x * 3).unwrap();
+    let _unrelated = reactor.map1(other, |x| x + 1).unwrap();
+    {
+        let seen = &seen;
+        reactor.add_global_callback(move |changes| seen.borrow_mut().push(changes.to_vec()));
+    }
+
+    assert!(reactor.set_value(input, 2));
+    assert_eq!(*seen.borrow(), vec![vec![(doubled, 4), (tripled, 6)]]);
+
+    // nothing changed, so nothing fires
+    assert!(reactor.set_value(input, 2));
+    assert_eq!(seen.borrow().len(), 1);
+
+    reactor.batch(|r| {
+        assert!(r.set_value(input, 3));
+        assert!(r.set_value(input, 4));
+    });
+    assert_eq!(seen.borrow().len(), 2);
+    assert_eq!(seen.borrow()[1], vec![(doubled, 8), (tripled, 12)]);
+}
+
+#[test]
+fn cloned_reactor_changes_independently_without_callbacks() {
+    let cb = CallbackRecorder::new();
+    let mut reactor = Reactor::new();
+    let input = reactor.create_input(1);
+    let doubled = reactor.map1(input, |x| x * 2).unwrap();
+    let total = reactor
+        .create_fold(&[CellID::Compute(doubled)], 0, |acc, v| acc + v[0])
+        .unwrap();
+    reactor
+        .add_callback(doubled, |v| cb.callback_called(v))
+        .unwrap();
+
+    let mut what_if = reactor.clone();
+    assert_eq!(what_if.callback_count(doubled), Some(0));
+    assert!(what_if.set_value(input, 5));
+    cb.expect_not_to_have_been_called();
+    assert_eq!(what_if.value(CellID::Compute(doubled)), Some(10));
+    assert_eq!(what_if.value(CellID::Compute(total)), Some(12));
+
+    // the original is untouched, fold state included
+    assert_eq!(reactor.value(CellID::Input(input)), Some(1));
+    assert_eq!(reactor.value(CellID::Compute(doubled)), Some(2));
+    assert_eq!(reactor.value(CellID::Compute(total)), Some(2));
+    assert!(reactor.set_value(input, 3));
+    cb.expect_to_have_been_called_with(6);
+    assert_eq!(reactor.value(CellID::Compute(total)), Some(8));
+}
+
+#[test]
+fn callback_reading_a_sibling_leaves_its_dependents_current() {
+    let seen = std::cell::RefCell::new(Vec::new());
+    let mut reactor = Reactor::new();
+    let a = reactor.create_input(1);
+    let b = reactor.map1(a, |x| x + 1).unwrap();
+    let c = reactor.map1(a, |x| x * 10).unwrap();
+    let d = reactor.map1(c, |x| x + 1).unwrap();
+    reactor
+        .add_callback_with_reactor(b, move |_, r| {
+            r.value(CellID::Compute(c));
+        })
+        .unwrap();
+    {
+        let seen = &seen;
+        reactor.add_global_callback(move |changes| seen.borrow_mut().extend_from_slice(changes));
+    }
+
+    assert!(reactor.set_value(a, 2));
+    assert_eq!(reactor.value(CellID::Compute(d)), Some(21));
+    assert_eq!(*seen.borrow(), vec![(b, 3), (c, 20), (d, 21)]);
+    assert!(reactor.set_value(a, 3));
+    assert_eq!(reactor.value(CellID::Compute(d)), Some(31));
+}
+
+#[test]
+fn cells_a_dropped_propagation_missed_stay_correct_after_another_set() {
+    let mut reactor = Reactor::new();
+    let a = reactor.create_input(1);
+    let x = reactor.create_input(100);
+    let b = reactor.map1(a, |v| v + 1).unwrap();
+    let c = reactor.map2(b, x, |v, w| v + w).unwrap();
+    drop(reactor.begin_set(a, 5));
+    assert!(reactor.set_value(x, 200));
+    assert_eq!(reactor.value(CellID::Compute(c)), Some(206));
+    assert_eq!(reactor.value(CellID::Compute(b)), Some(6));
+}
+
+#[test]
+fn scratch_evaluations_of_a_fold_give_its_current_value() {
+    let mut reactor = Reactor::new();
+    let input = reactor.create_input(5);
+    let sum = reactor
+        .create_fold(&[CellID::Input(input)], 0, |acc, v| acc + v[0])
+        .unwrap();
+    let doubled = reactor.map1(sum, |x| x * 2).unwrap();
+    let id = CellID::Compute(sum);
+    assert_eq!(reactor.value(id), Some(5));
+    assert_eq!(reactor.full_snapshot().get(id), Some(5));
+    assert_eq!(reactor.value_thunk(id).unwrap()(), Some(5));
+    assert_eq!(reactor.value_traced(id).unwrap().0, 5);
+    assert_eq!(reactor.evaluate_subset(&[sum])[&sum], 5);
+    assert_eq!(
+        reactor.value_traced(CellID::Compute(doubled)).unwrap().0,
+        10
+    );
+    assert_eq!(reactor.value(id), Some(5));
+}
+
+#[test]
+fn session_reads_of_a_fold_propagate_the_step() {
+    let cb = CallbackRecorder::new();
+    let mut reactor = Reactor::new();
+    let input = reactor.create_input(1);
+    let fold = reactor
+        .create_fold(&[CellID::Input(input)], 0, |acc, v| acc + v[0])
+        .unwrap();
+    let doubled = reactor.map1(fold, |x| x * 2).unwrap();
+    reactor
+        .add_callback(doubled, |v| cb.callback_called(v))
+        .unwrap();
+    assert_eq!(
+        reactor.value_with_policy(CellID::Compute(fold), CachePolicy::Session),
+        Some(2)
+    );
+    cb.expect_to_have_been_called_with(4);
+    assert_eq!(reactor.value(CellID::Compute(doubled)), Some(4));
+}
+
+#[test]
+fn snapshots_and_thunks_of_a_failing_cell_have_no_value() {
+    let mut reactor = Reactor::new();
+    let numerator = reactor.create_input(12);
+    let denominator = reactor.create_input(3);
+    let ratio = reactor
+        .create_fallible_compute(
+            &[CellID::Input(numerator), CellID::Input(denominator)],
+            |v| {
+                if v[1] == 0 {
+                    Err("division by zero")
+                } else {
+                    Ok(v[0] / v[1])
+                }
+            },
+        )
+        .unwrap();
+    let plus_one = reactor.map1(ratio, |x| x + 1).unwrap();
+    assert!(reactor.set_value(denominator, 0));
+
+    let snapshot = reactor.full_snapshot();
+    assert_eq!(snapshot.computes, vec![None, None]);
+    assert_eq!(snapshot.get(CellID::Compute(ratio)), None);
+    assert_eq!(snapshot.get(CellID::Input(numerator)), Some(12));
+    assert_eq!(
+        reactor.value_thunk(CellID::Compute(plus_one)).unwrap()(),
+        None
+    );
+
+    assert!(reactor.set_value(denominator, 4));
+    assert_eq!(
+        reactor.value_thunk(CellID::Compute(plus_one)).unwrap()(),
+        Some(4)
+    );
+}
+
+#[test]
+fn reactor_rebuilt_with_a_removed_cell_can_be_inspected() {
+    let mut reactor = Reactor::new();
+    let input = reactor.create_input(1);
+    let gone = reactor.map1(input, |x| x * 100).unwrap();
+    let kept = reactor.map1(input, |x| x + 1).unwrap();
+    assert_eq!(reactor.remove_compute(gone), Ok(()));
+
+    let restored = Reactor::from_snapshot(&reactor.to_snapshot(), |_| {
+        Box::new(|v: &[i32]| v[0] + 1) as BoxedComputeFn<i32>
+    })
+    .unwrap();
+    let snapshot = restored.full_snapshot();
+    assert_eq!(snapshot.computes, vec![None, Some(2)]);
+    assert_eq!(snapshot.get(CellID::Compute(kept)), Some(2));
+    assert_eq!(restored.value(CellID::Compute(gone)), None);
+    assert!(restored.validate_all().is_empty());
+    assert_eq!(restored.snapshot().len(), 2);
+}
+
+#[test]
+fn input_callbacks_fire_once_per_call_with_the_final_value() {
+    let seen = std::cell::RefCell::new(Vec::new());
+    let mut reactor = Reactor::new();
+    let a = reactor.create_input(1);
+    let b = reactor.create_input(2);
+    let sum = reactor.map2(a, b, |x, y| x + y).unwrap();
+    let cbid = {
+        let seen = &seen;
+        reactor
+            .add_input_callback(a, move |v| seen.borrow_mut().push(v))
+            .unwrap()
+    };
+
+    // written twice but back where it started, so it didn't change
+    assert!(reactor.set_values(&[(a, 5), (b, 3), (a, 1)]));
+    assert!(seen.borrow().is_empty());
+    assert_eq!(reactor.generation(CellID::Input(a)), Some(0));
+    assert_eq!(reactor.value(CellID::Compute(sum)), Some(4));
+
+    assert!(reactor.set_values(&[(a, 5), (a, 7)]));
+    assert_eq!(*seen.borrow(), vec![7]);
+
+    assert_eq!(reactor.remove_input_callback(a, cbid), Ok(()));
+    assert_eq!(
+        reactor.remove_input_callback(a, cbid),
+        Err(RemoveCallbackError::NonexistentCallback)
+    );
+    assert!(reactor.set_value(a, 8));
+    assert_eq!(*seen.borrow(), vec![7]);
+}
+
+#[test]
+fn lazy_reads_of_deep_stale_chains_succeed_the_first_time() {
+    let mut reactor = Reactor::new();
+    reactor.set_eager(false);
+    let input = reactor.create_input(1);
+    let mut last = CellID::Input(input);
+    for _ in 0..2 * MAX_EVAL_DEPTH {
+        last = CellID::Compute(reactor.create_compute(&[last], |v| v[0] + 1).unwrap());
+    }
+    assert!(reactor.set_value(input, 10));
+    let expected = 10 + 2 * MAX_EVAL_DEPTH as i32;
+    assert_eq!(reactor.try_value(last), Some(Ok(expected)));
+    assert!(reactor.set_value(input, 20));
+    assert_eq!(reactor.value(last), Some(expected + 10));
+}
+
+#[test]
+fn reads_nested_past_max_eval_depth_give_up_without_corrupting_cells() {
+    // each cell's callback reads the next cell, which is stale, so reading the first nests one
+    // read per cell
+    let run = || {
+        let saw_none = std::cell::Cell::new(false);
+        let mut reactor = Reactor::new();
+        reactor.set_eager(false);
+        let input = reactor.create_input(0);
+        let cells: Vec<_> = (0..MAX_EVAL_DEPTH as i32 + 8)
+            .map(|i| {
+                reactor
+                    .create_compute(&[CellID::Input(input)], move |v| v[0] + i)
+                    .unwrap()
+            })
+            .collect();
+        for pair in cells.windows(2) {
+            let next = CellID::Compute(pair[1]);
+            let saw_none = &saw_none;
+            reactor.add_callback_with_reactor(pair[0], move |_, reactor| {
+                if reactor.value(next).is_none() {
+                    saw_none.set(true);
+                }
+            });
+        }
+        assert!(reactor.set_value(input, 100));
+        assert_eq!(reactor.value(CellID::Compute(cells[0])), Some(100));
+        assert!(saw_none.get());
+        for (i, &cell) in cells.iter().enumerate() {
+            assert_eq!(reactor.value(CellID::Compute(cell)), Some(100 + i as i32));
+        }
+    };
+    std::thread::Builder::new()
+        .stack_size(256 << 20)
+        .spawn(run)
+        .unwrap()
+        .join()
+        .unwrap();
+}